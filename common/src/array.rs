@@ -22,15 +22,14 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-///
-///Fixed length array.
-///
-
 use core::slice::IterMut;
 use core::iter::Take;
 
 const SIZE: usize = 512;
 
+///
+///Fixed length array.
+///
 #[derive(Copy, Clone)]
 pub struct Array<S> where 
     S:Default + Copy + Clone + PartialEq
@@ -53,24 +52,27 @@ impl <I> Default for Array::<I> where
 impl <I> Array<I> where 
     I:Default + Copy + Clone + PartialEq
 {
+    #[allow(clippy::result_unit_err)]
     pub fn has(&self, conn: &I) -> Result<usize, ()> {
         for i in 0..self.cnt {
             if self.arr[i] == *conn {
                 return Ok(i);
             }
         }
-        return Err(());
+        Err(())
     }
     
+    #[allow(clippy::collapsible_if)]
     pub fn add(&mut self, conn: &I) {
         if self.cnt < SIZE {
-            if let Err(_) = self.has(conn) {
+            if self.has(conn).is_err() {
                 self.arr[self.cnt] = *conn;
                 self.cnt += 1;
             }
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     pub fn rmv_idx(&mut self, idx: usize) {
         if self.cnt > 0 {
             if idx < self.cnt {
@@ -24,10 +24,6 @@ SOFTWARE.
 
 use crate::offset::{Offset};
 
-///
-/// Fixed length Buffer.
-///
-
 /**********************************************************************
  * Buffer
  *********************************************************************/
@@ -66,6 +62,9 @@ pub trait BufferTrait<I>: Read<I> + Write<I> where
     I: Copy + Clone + Default
 {}
 
+///
+/// Fixed length Buffer.
+///
 #[derive(Clone, Copy)]
 pub struct Buffer<S> {
     rdpos: usize,
@@ -126,16 +125,16 @@ impl <I> Read<I> for Buffer<I> where
         }
     }
 
-    fn rewind(& mut self) -> () {
+    fn rewind(& mut self) {
         self.rdpos = 0;
     }
     
     fn empty(& mut self) -> bool {
-        (self.rdpos == self.wrpos)
+        self.rdpos == self.wrpos
     }
     
     fn empty_queue(& mut self) -> bool {
-        (self.amt == 0)
+        self.amt == 0
     }
 
     fn rdpos(&self) -> usize { 
@@ -147,7 +146,7 @@ impl <I> Read<I> for Buffer<I> where
 impl <I> Write<I> for Buffer<I> where
     I: Copy + Clone + Default
 {
-    fn enqueue(&mut self, val:I) -> () {
+    fn enqueue(&mut self, val:I) {
         if self.amt < BUFFER_LEN {
             self.buf[self.wrpos] = val;
             self.wrpos = Offset(self.wrpos).inc(BUFFER_LEN).val();
@@ -155,7 +154,7 @@ impl <I> Write<I> for Buffer<I> where
         }
     }
 
-    fn put(&mut self, val:I) -> () {
+    fn put(&mut self, val:I) {
         if self.full() {
             self.buf[BUFFER_LEN - 1] = val;
         } else {
@@ -164,7 +163,7 @@ impl <I> Write<I> for Buffer<I> where
         }
     }
 
-    fn fill(&mut self, val:I) -> () {
+    fn fill(&mut self, val:I) {
         for i in 0..BUFFER_LEN {
             self.buf[i] = val;
         }
@@ -172,21 +171,21 @@ impl <I> Write<I> for Buffer<I> where
         self.wrpos = BUFFER_LEN;
     }
 
-    fn reset(& mut self) -> () {
+    fn reset(& mut self) {
         self.rdpos = 0;
         self.wrpos = 0;
         self.amt   = 0;
     }
 
     fn full(& mut self) -> bool {
-        (self.wrpos == BUFFER_LEN)
+        self.wrpos == BUFFER_LEN
     }
 
     fn full_queue(& mut self) -> bool {
-        (self.amt == BUFFER_LEN)
+        self.amt == BUFFER_LEN
     }
 
-    fn copy_from(&mut self, from: &Buffer<I>) -> () {
+    fn copy_from(&mut self, from: &Buffer<I>) {
         self.buf = from.buf;
         self.rdpos = 0;
         self.wrpos = BUFFER_LEN;
@@ -225,7 +224,7 @@ pub fn apply<F: Copy> (bufs:  &mut[Buffer<F>],
                        left:  &[usize],
                        right: &[usize],
                        dest:  &[usize],
-                       f:     fn(F,F) -> F) -> ()
+                       f:     fn(F,F) -> F)
 {
     let mut maxlen:usize = 0;
 
@@ -270,7 +269,7 @@ pub fn apply<F: Copy> (bufs:  &mut[Buffer<F>],
 ///
 pub fn distribute<F: Copy> (bufs: &mut[Buffer<F>], 
                             map:  &[usize],
-                            f:    fn(F,F) -> F) -> ()
+                            f:    fn(F,F) -> F)
 {
     for dstidx in 0..bufs.len() {
         let srcidx = map[dstidx % map.len()];
@@ -401,9 +400,9 @@ mod tests {
         }
 
         distribute(& mut bufs, &[1], |_,b| b);
-        for i in 0..bufs.len() {
+        for buf in bufs.iter() {
             for j in 0..BUFFER_LEN {
-                assert!(bufs[i].buf[j] == fill[1]);
+                assert!(buf.buf[j] == fill[1]);
             }
         }
 
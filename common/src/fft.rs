@@ -0,0 +1,337 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Add, Sub, Mul};
+use libm::{sin, cos};
+
+/***********************************************************************
+ * Complex
+ **********************************************************************/
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    #[inline]
+    pub fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    #[inline]
+    pub fn conj(self) -> Complex {
+        Complex { re: self.re, im: -self.im }
+    }
+
+    #[inline]
+    pub fn scale(self, val: f32) -> Complex {
+        Complex { re: self.re * val, im: self.im * val }
+    }
+
+    #[inline]
+    pub fn norm_sqr(self) -> f32 {
+        (self.re * self.re) + (self.im * self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    #[inline]
+    fn add(self, rhs: Complex) -> Complex {
+        Complex { re: self.re + rhs.re, im: self.im + rhs.im }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    #[inline]
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex { re: self.re - rhs.re, im: self.im - rhs.im }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    #[inline]
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex { 
+            re: (self.re * rhs.re) - (self.im * rhs.im), 
+            im: (self.re * rhs.im) + (self.im * rhs.re),
+        }
+    }
+}
+
+///
+///e^(-i * 2pi * k / n). Calculated in double precision so large tables
+///stay accurate.
+///
+fn twiddle(k: usize, n: usize) -> Complex {
+    let ang = -2.0 * core::f64::consts::PI * (k as f64) / (n as f64);
+    Complex::new(cos(ang) as f32, sin(ang) as f32)
+}
+
+/***********************************************************************
+ * Fft
+ **********************************************************************/
+
+///
+///Radix 2 complex FFT of a fixed power of two length. Tables are 
+///allocated once by new() so forward() and inverse() don't allocate.
+///
+pub struct Fft {
+    len:  usize,
+    tw:   Vec<Complex>, //Twiddles for k = 0..len/2.
+    rev:  Vec<usize>,   //Bit reversed indexes.
+}
+
+impl Fft {
+///
+///New FFT of len points. Panics if len is not a power of two.
+///
+    pub fn new(len: usize) -> Fft {
+        assert!(len.is_power_of_two(), "fft::Fft::new(): Length must be a power of two.");
+
+        let bits = len.trailing_zeros();
+        let rev  = (0..len).map(|i| {
+            if bits == 0 { 0 } else { i.reverse_bits() >> ((core::mem::size_of::<usize>() * 8) as u32 - bits) }
+        }).collect();
+
+        Fft {
+            len,
+            tw:  (0..(len / 2)).map(|k| twiddle(k, len)).collect(),
+            rev,
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+///
+///In place forward transform. buf must be size() long.
+///
+    pub fn forward(&self, buf: &mut [Complex]) {
+        self.transform(buf, false);
+    }
+
+///
+///In place inverse transform. Not scaled, divide by size() to undo
+///forward().
+///
+    pub fn inverse(&self, buf: &mut [Complex]) {
+        self.transform(buf, true);
+    }
+
+    fn transform(&self, buf: &mut [Complex], inverse: bool) {
+        assert!(buf.len() == self.len, "fft::Fft::transform(): Buffer length mismatch.");
+
+        for i in 0..self.len {
+            let j = self.rev[i];
+            if i < j {
+                buf.swap(i, j);
+            }
+        }
+
+        let mut size = 2;
+        while size <= self.len {
+            let half = size / 2;
+            let step = self.len / size;
+
+            for start in (0..self.len).step_by(size) {
+                for k in 0..half {
+                    let w = if inverse { self.tw[k * step].conj() } else { self.tw[k * step] };
+                    let a = buf[start + k];
+                    let b = buf[start + k + half] * w;
+                    buf[start + k] = a + b;
+                    buf[start + k + half] = a - b;
+                }
+            }
+
+            size *= 2;
+        }
+    }
+}
+
+/***********************************************************************
+ * RealFft
+ **********************************************************************/
+
+///
+///FFT of len real values using a len/2 complex FFT. The spectrum is 
+///the len/2 + 1 bins from DC to Nyquist; the rest are conjugates.
+///
+pub struct RealFft {
+    len:  usize,
+    fft:  Fft,
+    tw:   Vec<Complex>,  //e^(-i * 2pi * k / len) for k = 0..len/2.
+    work: Vec<Complex>,  //Scratch of len/2.
+}
+
+impl RealFft {
+///
+///New real FFT of len points. Panics if len is not a power of two or
+///less than 2.
+///
+    pub fn new(len: usize) -> RealFft {
+        assert!(len >= 2 && len.is_power_of_two(), "fft::RealFft::new(): Length must be a power of two >= 2.");
+
+        RealFft {
+            len,
+            fft:  Fft::new(len / 2),
+            tw:   (0..(len / 2)).map(|k| twiddle(k, len)).collect(),
+            work: vec![Complex::default(); len / 2],
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+///
+///Number of spectrum bins, len/2 + 1.
+///
+    #[inline]
+    pub fn bins(&self) -> usize {
+        (self.len / 2) + 1
+    }
+
+///
+///Forward transform of input (size() values) into output (bins()).
+///
+    pub fn forward(&mut self, input: &[f32], output: &mut [Complex]) {
+        let half = self.len / 2;
+        assert!(input.len() == self.len && output.len() == half + 1, 
+                "fft::RealFft::forward(): Buffer length mismatch.");
+
+        for (k, z) in self.work.iter_mut().enumerate() {
+            *z = Complex::new(input[2 * k], input[(2 * k) + 1]);
+        }
+
+        self.fft.forward(&mut self.work);
+
+        for (k, out) in output.iter_mut().enumerate() {
+            let zk  = self.work[k % half];
+            let zn  = self.work[(half - k) % half].conj();
+            let e   = (zk + zn).scale(0.5);
+            let o   = (zk - zn).scale(0.5);
+            let o   = Complex::new(o.im, -o.re); //Divide by i.
+            let w   = if k < half { self.tw[k] } else { Complex::new(-1.0, 0.0) };
+            *out = e + (w * o);
+        }
+    }
+
+///
+///Inverse transform of input (bins()) into output (size() values). 
+///Scaled so inverse(forward(x)) == x.
+///
+    pub fn inverse(&mut self, input: &[Complex], output: &mut [f32]) {
+        let half = self.len / 2;
+        assert!(input.len() == half + 1 && output.len() == self.len, 
+                "fft::RealFft::inverse(): Buffer length mismatch.");
+
+        for k in 0..half {
+            let xk = input[k];
+            let xn = input[half - k].conj(); //X[k + len/2].
+            let e  = (xk + xn).scale(0.5);
+            let o  = (xk - xn).scale(0.5) * self.tw[k].conj();
+            self.work[k] = e + Complex::new(-o.im, o.re); //e + i*o.
+        }
+
+        self.fft.inverse(&mut self.work);
+
+        let scale = 1.0 / (half as f32);
+        for (k, z) in self.work.iter().enumerate() {
+            output[2 * k] = z.re * scale;
+            output[(2 * k) + 1] = z.im * scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fft::{Fft, RealFft, Complex, twiddle};
+    use alloc::vec::Vec;
+
+    fn dft(input: &[Complex]) -> Vec<Complex> {
+        let n = input.len();
+        (0..n).map(|k| {
+            input.iter().enumerate().fold(Complex::default(), |acc, (i, x)| {
+                acc + (*x * twiddle((i * k) % n, n))
+            })
+        }).collect()
+    }
+
+    fn signal(n: usize) -> Vec<f32> {
+        (0..n).map(|i| (((i * 7919) % 97) as f32 / 48.0) - 1.0).collect()
+    }
+
+    #[test]
+    fn complex() {
+        for &n in [1, 2, 8, 64, 256].iter() {
+            let sig = signal(2 * n);
+            let input: Vec<Complex> = (0..n).map(|i| Complex::new(sig[2 * i], sig[(2 * i) + 1])).collect();
+            let expect = dft(&input);
+
+            let fft = Fft::new(n);
+            let mut buf = input.clone();
+            fft.forward(&mut buf);
+            for (a, b) in buf.iter().zip(expect.iter()) {
+                assert!((*a - *b).norm_sqr() < 1e-6);
+            }
+
+            fft.inverse(&mut buf);
+            for (a, b) in buf.iter().zip(input.iter()) {
+                assert!((a.scale(1.0 / n as f32) - *b).norm_sqr() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn real() {
+        for &n in [2, 4, 16, 512].iter() {
+            let input = signal(n);
+            let cplx: Vec<Complex> = input.iter().map(|x| Complex::new(*x, 0.0)).collect();
+            let expect = dft(&cplx);
+
+            let mut fft = RealFft::new(n);
+            let mut spec = alloc::vec![Complex::default(); fft.bins()];
+            fft.forward(&input, &mut spec);
+            for (a, b) in spec.iter().zip(expect.iter()) {
+                assert!((*a - *b).norm_sqr() < 1e-6);
+            }
+
+            let mut out = alloc::vec![0.0; n];
+            fft.inverse(&spec, &mut out);
+            for (a, b) in out.iter().zip(input.iter()) {
+                assert!((a - b).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -23,6 +23,10 @@ SOFTWARE.
 */
 #![no_std]
 
+extern crate alloc;
+
 pub mod buffer;
 pub mod offset;
 pub mod array;
+pub mod fft;
+pub mod rng;
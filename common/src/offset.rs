@@ -38,7 +38,7 @@ impl From<usize> for Offset {
 
 impl Offset {
     pub fn inc(&mut self, lim: usize) -> &mut Self {
-        let mut new = self;
+        let new = self;
         new.0 += 1;
         if new.0 == lim {
             new.0 = 0; 
@@ -47,7 +47,7 @@ impl Offset {
     }
     
     pub fn add(&mut self, val: usize) -> &mut Self {
-        let mut new = self;
+        let new = self;
         new.0 += val;
         new
     }
@@ -56,7 +56,7 @@ impl Offset {
     ///If offset is >= limit then wrap.
     ///
     pub fn wrap(&mut self, lim: usize) -> &mut Self {
-        let mut new = self;
+        let new = self;
         let m = (new.0 + 1) % lim;
         if m > 0 {
             new.0 = m - 1;
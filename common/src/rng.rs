@@ -0,0 +1,100 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+///
+///Xorshift pseudo random numbers. Fast and good enough for audio noise
+///and dither. Not for anything needing real randomness.
+///
+pub struct Rng {
+    state: u32,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0x2545_f491)
+    }
+}
+
+impl Rng {
+///
+///New generator from seed. A zero seed is replaced since xorshift 
+///would only ever return zero.
+///
+    pub fn new(seed: u32) -> Rng {
+        Rng { state: if seed == 0 { 0x2545_f491 } else { seed } }
+    }
+
+    #[inline]
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+///
+///Uniform 0..1.
+///
+    #[inline]
+    pub fn next_f32(&mut self) -> f32 {
+        ((self.next_u32() >> 8) as f32) * (1.0 / 16_777_216.0)
+    }
+
+///
+///Uniform -1..1.
+///
+    #[inline]
+    pub fn next_bipolar(&mut self) -> f32 {
+        (self.next_f32() * 2.0) - 1.0
+    }
+
+///
+///Triangular -1..1 from two uniforms, as used for TPDF dither.
+///
+    #[inline]
+    pub fn next_triangular(&mut self) -> f32 {
+        self.next_f32() - self.next_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::Rng;
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(0);
+        let mut sum = 0.0;
+        for _ in 0..100000 {
+            let u = rng.next_f32();
+            let t = rng.next_triangular();
+            assert!((0.0..1.0).contains(&u));
+            assert!(t > -1.0 && t < 1.0);
+            sum += rng.next_bipolar();
+        }
+        assert!((sum / 100000.0f32).abs() < 0.01);
+    }
+}
@@ -0,0 +1,490 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::{sinf, cosf, powf, sqrtf};
+use core::f32::consts::{FRAC_1_SQRT_2, PI};
+
+///
+///Maximum number of cascaded sections. Each adds 12dB/octave of slope
+///to the pass/stop filters.
+///
+pub const MAX_STAGES: usize = 4;
+
+static PARAMS: [Desc; 5] = [
+    Desc { name: "mode",   min: 0.0,   max: 6.0,     default: 0.0,    unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "freq",   min: 20.0,  max: 20000.0, default: 1000.0, unit: Unit::Hz,   curve: Curve::Log },
+    Desc { name: "q",      min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "gain",   min: -24.0, max: 24.0,    default: 0.0,    unit: Unit::Db,   curve: Curve::Linear },
+    Desc { name: "stages", min: 1.0,   max: MAX_STAGES as SampleType, default: 1.0, unit: Unit::None, curve: Curve::Stepped },
+];
+
+/***********************************************************************
+ * Mode
+ **********************************************************************/
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    LowPass,
+    HighPass,
+    BandPass,  //Constant 0dB peak gain.
+    Notch,
+    Peak,      //Uses gain.
+    LowShelf,  //Uses gain.
+    HighShelf, //Uses gain.
+}
+
+impl Mode {
+///
+///Mode from parameter value. Values are rounded and out of range 
+///values pick the nearest mode.
+///
+    pub fn from_param(val: SampleType) -> Mode {
+        match (val + 0.5) as usize {
+            0 => Mode::LowPass,
+            1 => Mode::HighPass,
+            2 => Mode::BandPass,
+            3 => Mode::Notch,
+            4 => Mode::Peak,
+            5 => Mode::LowShelf,
+            _ => Mode::HighShelf,
+        }
+    }
+
+    pub fn to_param(&self) -> SampleType {
+        match self {
+            Mode::LowPass   => 0.0,
+            Mode::HighPass  => 1.0,
+            Mode::BandPass  => 2.0,
+            Mode::Notch     => 3.0,
+            Mode::Peak      => 4.0,
+            Mode::LowShelf  => 5.0,
+            Mode::HighShelf => 6.0,
+        }
+    }
+}
+
+/***********************************************************************
+ * Coefs
+ **********************************************************************/
+
+///
+///Biquad coefficients normalized so a0 = 1.
+///
+#[derive(Clone, Copy)]
+pub struct Coefs {
+    pub b0: SampleType,
+    pub b1: SampleType,
+    pub b2: SampleType,
+    pub a1: SampleType,
+    pub a2: SampleType,
+}
+
+impl Default for Coefs {
+///
+///Pass through.
+///
+    fn default() -> Self {
+        Coefs { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 }
+    }
+}
+
+impl Coefs {
+///
+///Coefficients from the RBJ Audio EQ Cookbook. Frequency is limited to
+///just below Nyquist and q to a small positive value so modulated 
+///values can't produce an unstable filter.
+///
+    pub fn new(mode: Mode, 
+               freq: SampleType, 
+               q: SampleType, 
+               gain: SampleType, 
+               rate: SampleType) -> Coefs 
+    {
+        let nyquist = rate * 0.49;
+        let freq = if freq < 1.0 { 1.0 } else if freq > nyquist { nyquist } else { freq };
+        let q    = if q < 0.01 { 0.01 } else { q };
+
+        let w0    = 2.0 * PI * freq / rate;
+        let cosw  = cosf(w0);
+        let alpha = sinf(w0) / (2.0 * q);
+        let a     = powf(10.0, gain / 40.0);
+
+        let (b0, b1, b2, a0, a1, a2) = match mode {
+            Mode::LowPass => (
+                (1.0 - cosw) / 2.0, 1.0 - cosw, (1.0 - cosw) / 2.0,
+                1.0 + alpha, -2.0 * cosw, 1.0 - alpha
+            ),
+            Mode::HighPass => (
+                (1.0 + cosw) / 2.0, -(1.0 + cosw), (1.0 + cosw) / 2.0,
+                1.0 + alpha, -2.0 * cosw, 1.0 - alpha
+            ),
+            Mode::BandPass => (
+                alpha, 0.0, -alpha,
+                1.0 + alpha, -2.0 * cosw, 1.0 - alpha
+            ),
+            Mode::Notch => (
+                1.0, -2.0 * cosw, 1.0,
+                1.0 + alpha, -2.0 * cosw, 1.0 - alpha
+            ),
+            Mode::Peak => (
+                1.0 + (alpha * a), -2.0 * cosw, 1.0 - (alpha * a),
+                1.0 + (alpha / a), -2.0 * cosw, 1.0 - (alpha / a)
+            ),
+            Mode::LowShelf => {
+                let s = 2.0 * sqrtf(a) * alpha;
+                (
+                    a * ((a + 1.0) - ((a - 1.0) * cosw) + s),
+                    2.0 * a * ((a - 1.0) - ((a + 1.0) * cosw)),
+                    a * ((a + 1.0) - ((a - 1.0) * cosw) - s),
+                    (a + 1.0) + ((a - 1.0) * cosw) + s,
+                    -2.0 * ((a - 1.0) + ((a + 1.0) * cosw)),
+                    (a + 1.0) + ((a - 1.0) * cosw) - s
+                )
+            },
+            Mode::HighShelf => {
+                let s = 2.0 * sqrtf(a) * alpha;
+                (
+                    a * ((a + 1.0) + ((a - 1.0) * cosw) + s),
+                    -2.0 * a * ((a - 1.0) + ((a + 1.0) * cosw)),
+                    a * ((a + 1.0) + ((a - 1.0) * cosw) - s),
+                    (a + 1.0) - ((a - 1.0) * cosw) + s,
+                    2.0 * ((a - 1.0) - ((a + 1.0) * cosw)),
+                    (a + 1.0) - ((a - 1.0) * cosw) - s
+                )
+            },
+        };
+
+        Coefs {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+///
+///Magnitude of the response at freq. Useful for drawing curves.
+///
+    pub fn magnitude(&self, freq: SampleType, rate: SampleType) -> SampleType {
+        let w = 2.0 * PI * freq / rate;
+        let (c1, s1) = (cosf(w), sinf(w));
+        let (c2, s2) = (cosf(2.0 * w), sinf(2.0 * w));
+
+        let nr = self.b0 + (self.b1 * c1) + (self.b2 * c2);
+        let ni = -(self.b1 * s1) - (self.b2 * s2);
+        let dr = 1.0 + (self.a1 * c1) + (self.a2 * c2);
+        let di = -(self.a1 * s1) - (self.a2 * s2);
+
+        sqrtf(((nr * nr) + (ni * ni)) / ((dr * dr) + (di * di)))
+    }
+}
+
+/***********************************************************************
+ * Section
+ **********************************************************************/
+
+///
+///One biquad section. Transposed direct form II holds up well when
+///coefficients change every sample.
+///
+#[derive(Clone, Copy, Default)]
+pub struct Section {
+    z1: SampleType,
+    z2: SampleType,
+}
+
+impl Section {
+    #[inline]
+    pub fn process(&mut self, c: &Coefs, x: SampleType) -> SampleType {
+        let y = (c.b0 * x) + self.z1;
+        self.z1 = (c.b1 * x) - (c.a1 * y) + self.z2;
+        self.z2 = (c.b2 * x) - (c.a2 * y);
+
+//Recover from a blow up rather than output garbage forever.
+        if !self.z1.is_finite() || !self.z2.is_finite() {
+            self.reset();
+            return 0.0;
+        }
+
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/***********************************************************************
+ * Biquad
+ **********************************************************************/
+
+///
+///Biquad filter effect. Stages cascades identical sections for steeper
+///slopes (stages * 12dB/octave).
+///
+pub struct Biquad {
+    mode:     Mode,
+    freq:     Smooth, //Cutoff/center frequency in Hz.
+    q:        Smooth, //Resonance/bandwidth.
+    gain:     Smooth, //Gain in dB for peak and shelf modes.
+    stages:   usize,  //Number of cascaded sections.
+    rate:     SampleType,
+    dirty:    bool,   //Coefficients need recalculating.
+    coefs:    Coefs,
+    sections: [Section; MAX_STAGES],
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Biquad {
+            mode:     Mode::LowPass,
+            freq:     Smooth::new(1000.0),
+            q:        Smooth::new(FRAC_1_SQRT_2),
+            gain:     Smooth::new(0.0),
+            stages:   1,
+            rate:     SAMPLE_RATE,
+            dirty:    true,
+            coefs:    Coefs::default(),
+            sections: [Section::default(); MAX_STAGES],
+        }
+    }
+}
+
+impl Effect for Biquad {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.mode = Mode::LowPass;
+        self.freq.reset(1000.0);
+        self.q.reset(FRAC_1_SQRT_2);
+        self.gain.reset(0.0);
+        self.stages = 1;
+        self.dirty = true;
+        for section in self.sections.iter_mut() {
+            section.reset();
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.freq.sample_rate(sample_rate);
+        self.q.sample_rate(sample_rate);
+        self.gain.sample_rate(sample_rate);
+        self.dirty = true;
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.mode(Mode::from_param(val)); },
+            1 => { self.freq(val); },
+            2 => { self.q(val); },
+            3 => { self.gain(val); },
+            4 => { self.stages((val + 0.5) as usize); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.mode.to_param()),
+            1 => Ok(self.freq.target()),
+            2 => Ok(self.q.target()),
+            3 => Ok(self.gain.target()),
+            4 => Ok(self.stages as SampleType),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Biquad {
+///
+///Filter one sample. Coefficients are only recalculated while a 
+///parameter is moving.
+///
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        if self.dirty {
+            self.coefs = Coefs::new(self.mode,
+                                    self.freq.next(),
+                                    self.q.next(),
+                                    self.gain.next(),
+                                    self.rate);
+
+            self.dirty = !(self.freq.settled() && 
+                           self.q.settled() && 
+                           self.gain.settled());
+        }
+
+        let mut smpl = smpl_in;
+        for section in self.sections[..self.stages].iter_mut() {
+            smpl = section.process(&self.coefs, smpl);
+        }
+        smpl
+    }
+
+///
+///True once parameters have reached their targets and coefficients
+///are no longer being recalculated.
+///
+    #[inline]
+    pub fn settled(&self) -> bool {
+        !self.dirty
+    }
+
+    pub fn mode(&mut self, mode: Mode) -> &mut Self {
+        let new = self;
+        new.mode  = mode;
+        new.dirty = true;
+        new
+    }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq.set(hz);
+        new.dirty = true;
+        new
+    }
+
+    pub fn q(&mut self, q: SampleType) -> &mut Self {
+        let new = self;
+        new.q.set(q);
+        new.dirty = true;
+        new
+    }
+
+    pub fn gain(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.gain.set(db);
+        new.dirty = true;
+        new
+    }
+
+///
+///Jump frequency, q and gain to their targets instead of ramping.
+///
+    pub fn settle(&mut self) -> &mut Self {
+        let new = self;
+        new.freq.reset(new.freq.target());
+        new.q.reset(new.q.target());
+        new.gain.reset(new.gain.target());
+        new.dirty = true;
+        new
+    }
+
+///
+///Number of cascaded sections 1..MAX_STAGES. Newly added sections 
+///start from silence.
+///
+    pub fn stages(&mut self, stages: usize) -> &mut Self {
+        let new = self;
+        let stages = stages.clamp(1, MAX_STAGES);
+
+        for section in new.sections[new.stages.min(stages)..].iter_mut() {
+            section.reset();
+        }
+
+        new.stages = stages;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::biquad::{Coefs, Mode, Biquad};
+    use crate::{Effect, SAMPLE_RATE};
+    use core::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn response() {
+        let lp = Coefs::new(Mode::LowPass, 1000.0, FRAC_1_SQRT_2, 0.0, SAMPLE_RATE);
+        assert!((lp.magnitude(10.0, SAMPLE_RATE) - 1.0).abs() < 0.001);
+        assert!((lp.magnitude(1000.0, SAMPLE_RATE) - FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(lp.magnitude(10000.0, SAMPLE_RATE) < 0.02);
+
+        let hp = Coefs::new(Mode::HighPass, 1000.0, FRAC_1_SQRT_2, 0.0, SAMPLE_RATE);
+        assert!(hp.magnitude(10.0, SAMPLE_RATE) < 0.001);
+        assert!((hp.magnitude(10000.0, SAMPLE_RATE) - 1.0).abs() < 0.01);
+
+        let pk = Coefs::new(Mode::Peak, 1000.0, 1.0, 6.0, SAMPLE_RATE);
+        assert!((pk.magnitude(1000.0, SAMPLE_RATE) - 1.9953).abs() < 0.01);
+
+        let ls = Coefs::new(Mode::LowShelf, 1000.0, FRAC_1_SQRT_2, -12.0, SAMPLE_RATE);
+        assert!((ls.magnitude(10.0, SAMPLE_RATE) - 0.2512).abs() < 0.01);
+        assert!((ls.magnitude(20000.0, SAMPLE_RATE) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn cascade() {
+//DC passes unchanged through every stage of a low pass.
+        let mut bq = Biquad::default();
+        bq.reset();
+        bq.stages(4);
+        let mut out = 0.0;
+        for _ in 0..10000 {
+            out = bq.process(1.0);
+        }
+        assert!((out - 1.0).abs() < 0.001);
+
+//Modulating every sample stays stable.
+        bq.stages(2).q(10.0);
+        for i in 0..10000 {
+            bq.freq(100.0 + ((i % 1000) as f32) * 15.0);
+            out = bq.process(if i % 2 == 0 { 1.0 } else { -1.0 });
+            assert!(out.is_finite());
+        }
+    }
+
+    #[test]
+    fn settles() {
+//Coefficients stop being recalculated once a move has finished.
+        for freq in [100.0, 250.0, 4000.0, 10000.0].iter() {
+            let mut bq = Biquad::default();
+            bq.freq(*freq).q(2.0).gain(3.0);
+            assert!(!bq.settled());
+            for _ in 0..(SAMPLE_RATE as usize) {
+                bq.process(0.0);
+            }
+            assert!(bq.settled());
+        }
+    }
+}
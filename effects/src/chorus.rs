@@ -0,0 +1,97 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use crate::moddelay::{Sweep, SweptDelay};
+use crate::param::{Desc, Unit, Curve};
+use crate::wave::SHAPE_MAX;
+
+///
+///Longest base delay and depth in seconds.
+///
+const DELAY_SECONDS: SampleType = 0.05;
+const DEPTH_SECONDS: SampleType = 0.01;
+
+static PARAMS: [Desc; 6] = [
+    Desc { name: "rate",     min: 0.01,  max: 10.0,          default: 0.8,   unit: Unit::Hz,      curve: Curve::Log },
+    Desc { name: "depth",    min: 0.0,   max: DEPTH_SECONDS, default: 0.003, unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "shape",    min: 0.0,   max: SHAPE_MAX,     default: 0.0,   unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "delay",    min: 0.005, max: DELAY_SECONDS, default: 0.02,  unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "feedback", min: 0.0,   max: 0.9,           default: 0.0,   unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "mix",      min: 0.0,   max: 1.0,           default: 0.5,   unit: Unit::Ratio,   curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Chorus
+ **********************************************************************/
+
+///
+///Chorus. Three voices swept by one LFO a third of a cycle apart.
+///
+pub type Chorus = SweptDelay<ChorusSweep>;
+
+pub struct ChorusSweep;
+
+impl Sweep for ChorusSweep {
+    fn params() -> &'static [Desc] { &PARAMS }
+    fn voices() -> usize { 3 }
+    fn max_seconds() -> SampleType { DELAY_SECONDS + DEPTH_SECONDS }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chorus::Chorus;
+    use crate::{Effect, SAMPLE_RATE};
+
+    #[test]
+    fn chorus() {
+//Fully wet with no sweep, an impulse comes out delay seconds later.
+        let mut ch = Chorus::default();
+        ch.core().depth(0.0).mix(1.0).settle();
+        let mut out = [0.0; 2400];
+        for (n, smpl) in out.iter_mut().enumerate() {
+            *smpl = ch.process(if n == 0 { 1.0 } else { 0.0 });
+        }
+        let peak = out.iter().enumerate().fold(0, |p, (n, s)| if s.abs() > out[p].abs() { n } else { p });
+        assert!(peak == (0.02 * SAMPLE_RATE) as usize);
+
+//Sweeping moves the delay, so a steady tone comes out changing.
+        let mut ch = Chorus::default();
+        ch.core().mix(1.0).freq(5.0).settle();
+        let tone: Vec<f32> = (0..48000).map(|n| (n as f32 * 0.05).sin()).collect();
+        let mut wet = vec![0.0; 48000];
+        ch.process_block(&tone, &mut wet);
+        let mut dry = Chorus::default();
+        dry.core().depth(0.0).mix(1.0).settle();
+        let mut fixed = vec![0.0; 48000];
+        dry.process_block(&tone, &mut fixed);
+        assert!(wet.iter().zip(fixed.iter()).skip(4800).any(|(a, b)| (a - b).abs() > 0.1));
+
+//Mix 0 is the dry signal.
+        let mut ch = Chorus::default();
+        ch.core().mix(0.0).settle();
+        ch.process_block(&tone, &mut wet);
+        assert!(wet.iter().zip(tone.iter()).all(|(a, b)| a == b));
+    }
+}
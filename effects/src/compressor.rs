@@ -0,0 +1,516 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::delayline::DelayLine;
+use crate::detector::{Detector, Mode};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use alloc::vec;
+use alloc::vec::Vec;
+use libm::{expf, fabsf, log10f, powf};
+
+///
+///Lookahead of the limiter. Output is delayed by this much in limit 
+///mode.
+///
+const LOOKAHEAD_SECONDS: SampleType = 0.005;
+
+///
+///Levels are floored here before converting to dB.
+///
+const FLOOR_DB: SampleType = -120.0;
+
+static PARAMS: [Desc; 8] = [
+    Desc { name: "threshold", min: -60.0,  max: 0.0,  default: -20.0, unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "ratio",     min: 1.0,    max: 20.0, default: 4.0,   unit: Unit::Ratio,   curve: Curve::Log },
+    Desc { name: "knee",      min: 0.0,    max: 24.0, default: 6.0,   unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "attack",    min: 0.0001, max: 0.5,  default: 0.01,  unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "release",   min: 0.005,  max: 2.0,  default: 0.1,   unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "makeup",    min: 0.0,    max: 24.0, default: 0.0,   unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "detector",  min: 0.0,    max: 1.0,  default: 1.0,   unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "limit",     min: 0.0,    max: 1.0,  default: 0.0,   unit: Unit::None,    curve: Curve::Stepped },
+];
+
+#[inline]
+fn to_db(val: SampleType) -> SampleType {
+    if val > 0.0 {
+        let db = 20.0 * log10f(val);
+        if db > FLOOR_DB { db } else { FLOOR_DB }
+    } else {
+        FLOOR_DB
+    }
+}
+
+#[inline]
+fn from_db(db: SampleType) -> SampleType {
+    powf(10.0, db / 20.0)
+}
+
+/***********************************************************************
+ * MinHold
+ **********************************************************************/
+
+///
+///Minimum of the last len values. Monotonic queue so each value is
+///pushed and popped at most once.
+///
+struct MinHold {
+    vals: Vec<(SampleType, usize)>, //Ring of candidate (value, count).
+    head: usize,                    //Oldest candidate.
+    num:  usize,                    //Number of candidates.
+    len:  usize,                    //Window length.
+    cnt:  usize,                    //Values pushed.
+}
+
+impl MinHold {
+    fn new(len: usize) -> Self {
+        MinHold {
+            vals: vec![(1.0, 0); len + 1],
+            head: 0,
+            num:  0,
+            len,
+            cnt:  0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.num  = 0;
+        self.cnt  = 0;
+    }
+
+    #[inline]
+    fn next(&mut self, val: SampleType) -> SampleType {
+        let cap = self.vals.len();
+
+        while self.num > 0 && self.vals[(self.head + self.num - 1) % cap].0 >= val {
+            self.num -= 1;
+        }
+
+        self.vals[(self.head + self.num) % cap] = (val, self.cnt);
+        self.num += 1;
+
+        while self.vals[self.head].1 + self.len <= self.cnt {
+            self.head = (self.head + 1) % cap;
+            self.num -= 1;
+        }
+
+        self.cnt = self.cnt.wrapping_add(1);
+        self.vals[self.head].0
+    }
+}
+
+/***********************************************************************
+ * Compressor
+ **********************************************************************/
+
+///
+///Feed forward compressor with a soft knee. In limit mode the ratio is 
+///infinite, the threshold is a ceiling the output never crosses and
+///the input is delayed by LOOKAHEAD_SECONDS so gain can come down 
+///before peaks arrive.
+///
+///Output port 0 is audio. Output port 1 is the current gain reduction
+///in dB (0 or more) for use as a modulation source.
+///
+pub struct Compressor {
+    threshold: Smooth,     //Threshold (ceiling in limit mode) in dB.
+    ratio:     SampleType, //Input:output ratio above threshold.
+    knee:      SampleType, //Knee width in dB.
+    attack:    SampleType, //Attack in seconds.
+    release:   SampleType, //Release in seconds.
+    makeup:    Smooth,     //Makeup gain in dB.
+    detect:    Mode,       //Detector mode for compression.
+    limit:     bool,       //Lookahead limiter mode.
+    rate:      SampleType, //Sample rate.
+
+    detector:  Detector,       //Compressor level.
+    line:      DelayLine,      //Lookahead delay.
+    hold:      MinHold,        //Lowest gain needed inside lookahead.
+    avg:       Vec<SampleType>,//Lookahead gains for averaging.
+    avg_pos:   usize,
+    avg_sum:   f64,
+    rel:       SampleType,     //Limiter release coefficient.
+    gain:      SampleType,     //Limiter gain.
+    gr:        SampleType,     //Last gain reduction in dB.
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        let mut comp = Compressor {
+            threshold: Smooth::new(-20.0),
+            ratio:     4.0,
+            knee:      6.0,
+            attack:    0.01,
+            release:   0.1,
+            makeup:    Smooth::new(0.0),
+            detect:    Mode::Rms,
+            limit:     false,
+            rate:      SAMPLE_RATE,
+            detector:  Detector::default(),
+            line:      DelayLine::new(0),
+            hold:      MinHold::new(1),
+            avg:       Vec::new(),
+            avg_pos:   0,
+            avg_sum:   0.0,
+            rel:       0.0,
+            gain:      1.0,
+            gr:        0.0,
+        };
+        comp.prepare(SAMPLE_RATE, 0);
+        comp.detector.mode(Mode::Rms);
+        comp
+    }
+}
+
+impl Effect for Compressor {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn num_outputs(&mut self) -> usize { 2 }
+
+///
+///Audio on port 0, gain reduction on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let input = match inputs.first() {
+            Some(input) => *input,
+            None => return,
+        };
+
+        match outputs {
+            [audio, gr, ..] => {
+                for ((smpl_out, gr_out), smpl_in) in audio.iter_mut()
+                                                          .zip(gr.iter_mut())
+                                                          .zip(input.iter())
+                {
+                    *smpl_out = self.tick(*smpl_in);
+                    *gr_out = self.gr;
+                }
+            },
+            [audio] => self.process_block(input, audio),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.threshold.reset(-20.0);
+        self.ratio = 4.0;
+        self.knee  = 6.0;
+        self.attack(0.01);
+        self.release(0.1);
+        self.makeup.reset(0.0);
+        self.detector(1.0);
+        self.limit = false;
+        self.clear();
+    }
+
+///
+///Lookahead buffers are sized for the sample rate here.
+///
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        let look = self.lookahead_len(sample_rate);
+
+        self.rate = sample_rate;
+        self.threshold.sample_rate(sample_rate);
+        self.makeup.sample_rate(sample_rate);
+        self.detector.sample_rate(sample_rate);
+        self.rel = expf(-1.0 / (self.release * sample_rate));
+
+        self.line = DelayLine::new(look);
+        self.hold = MinHold::new(look);
+        self.avg  = vec![1.0; look];
+        self.clear();
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.threshold(val); },
+            1 => { self.ratio(val); },
+            2 => { self.knee(val); },
+            3 => { self.attack(val); },
+            4 => { self.release(val); },
+            5 => { self.makeup(val); },
+            6 => { self.detector(val); },
+            7 => { self.limit(val >= 0.5); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.threshold.target()),
+            1 => Ok(self.ratio),
+            2 => Ok(self.knee),
+            3 => Ok(self.attack),
+            4 => Ok(self.release),
+            5 => Ok(self.makeup.target()),
+            6 => Ok(if self.detect == Mode::Rms { 1.0 } else { 0.0 }),
+            7 => Ok(if self.limit { 1.0 } else { 0.0 }),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Compressor {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let thr = self.threshold.next();
+        let makeup = from_db(self.makeup.next());
+
+        if self.limit {
+            let gain = self.limit_gain(smpl_in, from_db(thr));
+            self.gr = -to_db(gain);
+            self.line.write(smpl_in);
+            self.line.tap(self.avg.len()) * gain * makeup
+        } else {
+            let lvl = to_db(self.detector.next(smpl_in));
+            self.gr = self.reduction(lvl, thr);
+            smpl_in * from_db(-self.gr) * makeup
+        }
+    }
+
+///
+///Gain reduction in dB for a level in dB using a soft knee.
+///
+    #[inline]
+    fn reduction(&self, lvl: SampleType, thr: SampleType) -> SampleType {
+        let over  = lvl - thr;
+        let slope = 1.0 - (1.0 / self.ratio);
+
+        if (2.0 * over) <= -self.knee {
+            0.0
+        } else if (2.0 * fabsf(over)) < self.knee {
+            let x = over + (self.knee / 2.0);
+            slope * x * x / (2.0 * self.knee)
+        } else {
+            slope * over
+        }
+    }
+
+///
+///Limiter gain for the sample leaving the lookahead delay. The lowest
+///gain needed within the lookahead is averaged over the lookahead so
+///gain is fully down when the peak arrives. Rising gain follows the
+///release time.
+///
+    #[inline]
+    fn limit_gain(&mut self, smpl_in: SampleType, ceil: SampleType) -> SampleType {
+        let lvl = fabsf(smpl_in);
+        let need = if lvl > ceil { ceil / lvl } else { 1.0 };
+        let min = self.hold.next(need);
+
+        self.avg_sum += (min - self.avg[self.avg_pos]) as f64;
+        self.avg[self.avg_pos] = min;
+        self.avg_pos = (self.avg_pos + 1) % self.avg.len();
+
+        let target = (self.avg_sum / (self.avg.len() as f64)) as SampleType;
+
+        self.gain = if target < self.gain {
+            target
+        } else {
+            target + (self.rel * (self.gain - target))
+        };
+
+        if !self.gain.is_finite() {
+            self.gain = 1.0;
+        }
+
+        self.gain
+    }
+
+///
+///Lookahead in samples. At least 2 so there is a sample of latency to
+///work with.
+///
+    fn lookahead_len(&self, rate: SampleType) -> usize {
+        let len = (LOOKAHEAD_SECONDS * rate) as usize;
+        if len < 2 { 2 } else { len }
+    }
+
+    fn clear(&mut self) {
+        self.detector.reset();
+        self.line.clear();
+        self.hold.clear();
+        for smpl in self.avg.iter_mut() {
+            *smpl = 1.0;
+        }
+        self.avg_pos = 0;
+        self.avg_sum = self.avg.len() as f64;
+        self.gain = 1.0;
+        self.gr = 0.0;
+    }
+
+///
+///Current gain reduction in dB.
+///
+    pub fn reduction_db(&self) -> SampleType {
+        self.gr
+    }
+
+    pub fn threshold(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.threshold.set(db);
+        new
+    }
+
+    pub fn ratio(&mut self, ratio: SampleType) -> &mut Self {
+        let new = self;
+        new.ratio = if ratio < 1.0 { 1.0 } else { ratio };
+        new
+    }
+
+    pub fn knee(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.knee = if db < 0.0 { 0.0 } else { db };
+        new
+    }
+
+///
+///Compressor attack in seconds. Limit mode always attacks over the
+///lookahead.
+///
+    pub fn attack(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.attack = sec;
+        new.detector.attack(sec);
+        new
+    }
+
+    pub fn release(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.release = sec;
+        new.detector.release(sec);
+        new.rel = expf(-1.0 / (sec * new.rate));
+        new
+    }
+
+    pub fn makeup(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.makeup.set(db);
+        new
+    }
+
+///
+///Detector. 0 = peak, 1 = RMS.
+///
+    pub fn detector(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.detect = if val >= 0.5 { Mode::Rms } else { Mode::Peak };
+        new.detector.mode(new.detect);
+        new
+    }
+
+///
+///Lookahead limiter mode. Switching modes changes latency.
+///
+    pub fn limit(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        if new.limit != on {
+            new.limit = on;
+            new.clear();
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compressor::Compressor;
+    use crate::Effect;
+
+    #[test]
+    fn limit() {
+//Ceiling at -6dB holds against a burst well over it.
+        let mut comp = Compressor::default();
+        comp.limit(true).threshold(-6.0);
+        let ceil = 0.5012;
+
+        for i in 0..48000 {
+            let x = if i > 10000 && i < 20000 {
+                if i % 7 == 0 { 4.0 } else { -2.0 }
+            } else {
+                0.1
+            };
+            let y = comp.process(x);
+            assert!(y.abs() <= ceil);
+        }
+        assert!(comp.reduction_db() < 0.1);
+    }
+
+    #[test]
+    fn lookahead() {
+//A step over the ceiling is met by a ramp across the lookahead, not a
+//jump in gain.
+        let mut comp = Compressor::default();
+        comp.limit(true).threshold(-6.0);
+        for _ in 0..4800 {
+            comp.process(0.1);
+        }
+
+        let mut last = comp.reduction_db();
+        let mut ramp = 0;
+        for _ in 0..480 {
+            comp.process(1.0);
+            let gr = comp.reduction_db();
+            assert!(gr - last < 0.1);
+            if gr > 0.5 && gr < 5.5 {
+                ramp += 1;
+            }
+            last = gr;
+        }
+        assert!(ramp > 100);
+        assert!((last - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn ratio() {
+//Hard knee 4:1 takes 20dB over down to 5dB over.
+        let mut comp = Compressor::default();
+        comp.knee(0.0).detector(0.0).attack(0.0001);
+        let mut y = 0.0;
+        for _ in 0..48000 {
+            y = comp.process(1.0);
+        }
+        assert!((comp.reduction_db() - 15.0).abs() < 0.01);
+        assert!((y - 0.1778).abs() < 0.001);
+    }
+}
@@ -24,6 +24,13 @@ SOFTWARE.
 
 use super::SampleType;
 use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+
+static PARAMS: [Desc; 1] = [
+    Desc { name: "val", min: -20000.0, max: 20000.0, default: 0.0, unit: Unit::None, curve: Curve::Linear },
+];
 
 /***********************************************************************
  * Constant
@@ -31,7 +38,7 @@ use crate::Effect;
 
 #[derive(Default)]
 pub struct Constant {
-    pub val: SampleType, //Feedback.
+    val: Smooth, //Constant value.
 }
 
 impl Effect for Constant {
@@ -39,24 +46,42 @@ impl Effect for Constant {
 ///Process.
 ///
     fn process(&mut self, _smpl_in: SampleType) -> SampleType {
-        self.val
+        self.val.next()
+    }
+
+///
+///Fill output block with constant value. Changes in value are ramped.
+///
+    fn process_block(&mut self, _input: &[SampleType], output: &mut [SampleType]) {
+        for smpl_out in output.iter_mut() {
+            *smpl_out = self.val.next();
+        }
     }
 
 ///
 ///Reset delay to defaults.
 ///
     fn reset(&mut self) {
-        self.val = SampleType::default();
+        self.val.reset(SampleType::default());
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.val.sample_rate(sample_rate);
     }
 
-    fn num_params(&mut self) -> usize { 1 }
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
 
-    fn set_param(&mut self, _idx: usize, val: SampleType) {
-        self.val = val;
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        self.val(val);
+        Ok(())
     }
 
-    fn get_param(&mut self, _idx: usize) -> SampleType { 
-        self.val
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> { 
+        match idx {
+            0 => Ok(self.val.target()),
+            _ => Err(ERROR::INDEX)
+        }
     }
 }
 
@@ -65,8 +90,8 @@ impl Constant {
 ///Set constant value.
 ///
     pub fn val(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.val = val;
+        let new = self;
+        new.val.set(val);
         new
     }
 }
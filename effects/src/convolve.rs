@@ -0,0 +1,365 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use common::fft::{RealFft, Complex};
+use alloc::vec;
+use alloc::vec::Vec;
+use libm::powf;
+
+///
+///Default partition length in samples. Also the latency.
+///
+pub const PARTITION: usize = 128;
+
+const MIN_PARTITION: usize = 16;
+const MAX_PARTITION: usize = 4096;
+
+static PARAMS: [Desc; 3] = [
+    Desc { name: "gain", min: -48.0, max: 24.0, default: 0.0, unit: Unit::Db,    curve: Curve::Linear },
+    Desc { name: "wet",  min: 0.0,   max: 1.0,  default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "dry",  min: 0.0,   max: 1.0,  default: 0.0, unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Convolve
+ **********************************************************************/
+
+///
+///Uniformly partitioned overlap-save convolution. The impulse response
+///is split into partitions of B samples, each transformed once when 
+///loaded. Every B input samples one 2B point FFT, a multiply-add per 
+///partition and one inverse FFT produce the next B output samples, so
+///the wet output is B samples late.
+///
+///Only partition 0 needs the newest input. The multiply-adds for the 
+///older partitions are spread evenly over the B samples before they 
+///are needed, so the worst case sample costs one forward and one 
+///inverse 2B point FFT plus ceil((partitions - 1) / B) + 1 partition
+///multiply-adds, however long the IR. Short partitions (64..128) suit 
+///speaker cabinet IRs; long room IRs use longer partitions (512..1024)
+///to keep the number of partitions down.
+///
+///IRs are not resampled so should be recorded at the running rate.
+///
+pub struct Convolve {
+    gain_db:   SampleType,
+    gain:      Smooth,          //IR gain as linear.
+    wet:       Smooth,
+    dry:       Smooth,
+
+    partition: usize,           //B.
+    parts:     usize,           //Number of IR partitions.
+    fft:       RealFft,         //2B points.
+    ir:        Vec<Complex>,    //IR partition spectra, B + 1 bins each.
+    fdl:       Vec<Complex>,    //Input spectra, newest at slot.
+    slot:      usize,
+    acc:       Vec<Complex>,    //Spectrum accumulator.
+    tail:      Vec<Complex>,    //Older partitions summed for the next block.
+    done:      usize,           //Older partitions summed so far.
+    time:      Vec<SampleType>, //Inverse FFT result.
+    input:     Vec<SampleType>, //Last 2B input samples.
+    output:    Vec<SampleType>, //B output samples.
+    pos:       usize,           //Position within the partition.
+}
+
+impl Default for Convolve {
+    fn default() -> Self {
+        let mut conv = Convolve {
+            gain_db:   0.0,
+            gain:      Smooth::new(1.0),
+            wet:       Smooth::new(1.0),
+            dry:       Smooth::new(0.0),
+            partition: 0,
+            parts:     0,
+            fft:       RealFft::new(2),
+            ir:        Vec::new(),
+            fdl:       Vec::new(),
+            slot:      0,
+            acc:       Vec::new(),
+            tail:      Vec::new(),
+            done:      0,
+            time:      Vec::new(),
+            input:     Vec::new(),
+            output:    Vec::new(),
+            pos:       0,
+        };
+        conv.load(&[1.0], PARTITION);
+        conv
+    }
+}
+
+impl Effect for Convolve {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+///
+///Reset levels and clear history. The loaded IR is kept.
+///
+    fn reset(&mut self) {
+        self.gain(0.0);
+        self.gain.reset(1.0);
+        self.wet.reset(1.0);
+        self.dry.reset(0.0);
+        self.clear();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.gain.sample_rate(sample_rate);
+        self.wet.sample_rate(sample_rate);
+        self.dry.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.gain(val); },
+            1 => { self.wet(val); },
+            2 => { self.dry(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.gain_db),
+            1 => Ok(self.wet.target()),
+            2 => Ok(self.dry.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Convolve {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let b = self.partition;
+
+        self.input[b + self.pos] = smpl_in;
+        let smpl_wet = self.output[self.pos];
+
+//Share of the older partitions due by the end of this sample.
+        let due = ((self.parts - 1) * (self.pos + 1) + b - 1) / b;
+        while self.done < due {
+            self.done += 1;
+            let p = self.done;
+            self.accumulate(p);
+        }
+
+        self.pos += 1;
+        if self.pos == b {
+            self.pos = 0;
+            self.block();
+        }
+
+        (smpl_wet * self.gain.next() * self.wet.next()) + (smpl_in * self.dry.next())
+    }
+
+///
+///Add partition p times the input spectrum it pairs with in the next
+///block to the tail.
+///
+    #[inline]
+    fn accumulate(&mut self, p: usize) {
+        let bins = self.partition + 1;
+        let s    = (self.slot + self.parts - p) % self.parts;
+        let x    = &self.fdl[(s * bins)..((s + 1) * bins)];
+        let h    = &self.ir[(p * bins)..((p + 1) * bins)];
+
+        for ((acc, x), h) in self.tail.iter_mut().zip(x.iter()).zip(h.iter()) {
+            acc.re += (x.re * h.re) - (x.im * h.im);
+            acc.im += (x.re * h.im) + (x.im * h.re);
+        }
+    }
+
+///
+///Convolve the last B input samples. The older partitions are already
+///in the tail so only partition 0 is added here.
+///
+    fn block(&mut self) {
+        let b    = self.partition;
+        let bins = b + 1;
+
+        let slot = &mut self.fdl[(self.slot * bins)..((self.slot + 1) * bins)];
+        self.fft.forward(&self.input, slot);
+
+        let x = &self.fdl[(self.slot * bins)..((self.slot + 1) * bins)];
+        let h = &self.ir[..bins];
+
+        for (((acc, tail), x), h) in self.acc.iter_mut()
+                                             .zip(self.tail.iter_mut())
+                                             .zip(x.iter())
+                                             .zip(h.iter())
+        {
+            acc.re = tail.re + (x.re * h.re) - (x.im * h.im);
+            acc.im = tail.im + (x.re * h.im) + (x.im * h.re);
+            *tail  = Complex::default();
+        }
+
+        self.fft.inverse(&self.acc, &mut self.time);
+        self.output.copy_from_slice(&self.time[b..]);
+        self.input.copy_within(b.., 0);
+        self.slot = (self.slot + 1) % self.parts;
+        self.done = 0;
+    }
+
+///
+///Load an impulse response using partitions of partition samples. The
+///partition is rounded up to a power of two within 16..4096. 
+///Allocates so don't call from the audio path.
+///
+    pub fn load(&mut self, ir: &[SampleType], partition: usize) {
+        let b = partition.clamp(MIN_PARTITION, MAX_PARTITION).next_power_of_two();
+        let bins  = b + 1;
+        let parts = ((ir.len() + b - 1) / b).max(1);
+
+        self.partition = b;
+        self.parts     = parts;
+        self.fft       = RealFft::new(2 * b);
+        self.ir        = vec![Complex::default(); parts * bins];
+        self.fdl       = vec![Complex::default(); parts * bins];
+        self.acc       = vec![Complex::default(); bins];
+        self.tail      = vec![Complex::default(); bins];
+        self.time      = vec![0.0; 2 * b];
+        self.input     = vec![0.0; 2 * b];
+        self.output    = vec![0.0; b];
+
+//Each partition zero padded to 2B.
+        let mut pad = vec![0.0; 2 * b];
+        for (p, chunk) in ir.chunks(b).enumerate() {
+            for smpl in pad.iter_mut() { *smpl = 0.0; }
+            pad[..chunk.len()].copy_from_slice(chunk);
+            self.fft.forward(&pad, &mut self.ir[(p * bins)..((p + 1) * bins)]);
+        }
+
+        self.clear();
+    }
+
+///
+///Clear input and output history.
+///
+    pub fn clear(&mut self) {
+        for smpl in self.input.iter_mut().chain(self.output.iter_mut()) {
+            *smpl = 0.0;
+        }
+        for bin in self.fdl.iter_mut().chain(self.tail.iter_mut()) {
+            *bin = Complex::default();
+        }
+        self.done = 0;
+        self.slot = 0;
+        self.pos  = 0;
+    }
+
+///
+///Wet output delay in samples.
+///
+    pub fn latency(&self) -> usize {
+        self.partition
+    }
+
+///
+///IR gain in dB.
+///
+    pub fn gain(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.gain_db = db;
+        new.gain.set(powf(10.0, db / 20.0));
+        new
+    }
+
+    pub fn wet(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.wet.set(val);
+        new
+    }
+
+    pub fn dry(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.dry.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convolve::Convolve;
+    use crate::Effect;
+
+    #[test]
+    fn matches_direct() {
+        let ir: Vec<f32> = (0..300).map(|i| ((i * 37 % 23) as f32 / 11.0 - 1.0) / (1.0 + i as f32 * 0.05)).collect();
+        let input: Vec<f32> = (0..2000).map(|i| ((i * 7919 % 97) as f32 / 48.0) - 1.0).collect();
+
+        let mut conv = Convolve::default();
+        conv.load(&ir, 64);
+        let lat = conv.latency();
+        assert!(lat == 64);
+
+        for (n, x) in input.iter().enumerate() {
+            let y = conv.process(*x);
+            if n >= lat {
+                let m = n - lat;
+                let expect: f32 = (0..ir.len()).filter(|k| *k <= m).map(|k| ir[k] * input[m - k]).sum();
+                assert!((y - expect).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn spread() {
+//A long IR in short partitions. The older partitions are summed a few
+//per sample, never more than their share, and the output still 
+//matches.
+        let ir: Vec<f32> = (0..4000).map(|i| if i % 501 == 0 { 0.5 } else { 0.0 }).collect();
+        let mut conv = Convolve::default();
+        conv.load(&ir, 16);
+        let share = (conv.parts - 1 + 15) / 16;
+
+        let mut last = 0;
+        for n in 0..6000 {
+            let y = conv.process(if n == 0 { 1.0 } else { 0.0 });
+            let m = n as isize - 16;
+            let expect = if (0..4000).contains(&m) { ir[m as usize] } else { 0.0 };
+            assert!((y - expect).abs() < 1e-4);
+
+            let step = if conv.done >= last { conv.done - last } else { conv.done };
+            assert!(step <= share);
+            last = conv.done;
+        }
+    }
+}
@@ -0,0 +1,263 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use common::rng::Rng;
+use libm::{exp2f, floorf, roundf};
+
+static PARAMS: [Desc; 4] = [
+    Desc { name: "bits",       min: 1.0, max: 24.0, default: 8.0, unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "downsample", min: 1.0, max: 64.0, default: 4.0, unit: Unit::Ratio, curve: Curve::Log },
+    Desc { name: "dither",     min: 0.0, max: 1.0,  default: 0.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "mix",        min: 0.0, max: 1.0,  default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Crusher
+ **********************************************************************/
+
+///
+///Bit depth and sample rate reduction. The input is sampled and held
+///every downsample samples, which need not be a whole number, then 
+///quantized to bits. Fractional depths give in-between step sizes with
+///the whole steps centered and the leftover split between the -1 and
+///1 end levels, so the levels stay symmetric.
+///Bits and downsample take effect immediately so rack connections can
+///sweep them at audio rate.
+///
+pub struct Crusher {
+    bits:       SampleType, //Bit depth.
+    step:       SampleType, //Quantization step between 2^bits levels on -1..1.
+    steps:      SampleType, //Whole steps between the inner levels.
+    pad:        SampleType, //Distance from -1 and 1 to the inner levels.
+    downsample: SampleType, //Input samples per held sample.
+    dither:     SampleType, //TPDF dither in steps.
+    mix:        Smooth,     //0 dry .. 1 crushed.
+
+    phase:      SampleType, //Progress to the next hold 0..1.
+    held:       SampleType, //Current held sample.
+    rng:        Rng,
+}
+
+impl Default for Crusher {
+    fn default() -> Self {
+        let mut crusher = Crusher {
+            bits:       8.0,
+            step:       0.0,
+            steps:      0.0,
+            pad:        0.0,
+            downsample: 4.0,
+            dither:     0.0,
+            mix:        Smooth::new(1.0),
+            phase:      0.0,
+            held:       0.0,
+            rng:        Rng::default(),
+        };
+        crusher.reset();
+        crusher
+    }
+}
+
+impl Effect for Crusher {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.bits(8.0);
+        self.downsample(4.0);
+        self.dither(0.0);
+        self.mix.reset(1.0);
+        self.phase = 1.0;
+        self.held  = 0.0;
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.mix.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.bits(val); },
+            1 => { self.downsample(val); },
+            2 => { self.dither(val); },
+            3 => { self.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.bits),
+            1 => Ok(self.downsample),
+            2 => Ok(self.dither),
+            3 => Ok(self.mix.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Crusher {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            let dither = self.rng.next_triangular() * self.dither * self.step;
+            let smpl = smpl_in + dither;
+            self.held = if smpl <= -1.0 + (self.pad * 0.5) {
+                -1.0
+            } else if smpl >= 1.0 - (self.pad * 0.5) {
+                1.0
+            } else {
+                let lvl = roundf((smpl + 1.0 - self.pad) / self.step);
+                let lvl = if lvl < 0.0 { 0.0 } else if lvl > self.steps { self.steps } else { lvl };
+                (lvl * self.step) + self.pad - 1.0
+            };
+        }
+        self.phase += 1.0 / self.downsample;
+
+        let mix = self.mix.next();
+        (smpl_in * (1.0 - mix)) + (self.held * mix)
+    }
+
+///
+///Bit depth. Output has 2^bits levels from -1 to 1, so 1 bit is 
+///just -1 and 1. Fractional values are allowed and keep -1 and 1 as
+///the end levels.
+///
+    pub fn bits(&mut self, bits: SampleType) -> &mut Self {
+        let new = self;
+        let bits = if bits < 1.0 { 1.0 } else { bits };
+        new.bits = bits;
+        new.step = 2.0 / (exp2f(bits) - 1.0);
+//Tolerance keeps whole bit depths from losing a step to rounding.
+        new.steps = floorf((2.0 / new.step) + 0.0001);
+        new.pad = ((2.0 - (new.steps * new.step)) * 0.5).max(0.0);
+        new
+    }
+
+///
+///Sample rate reduction ratio, 1 is none.
+///
+    pub fn downsample(&mut self, ratio: SampleType) -> &mut Self {
+        let new = self;
+        new.downsample = if ratio < 1.0 { 1.0 } else { ratio };
+        new
+    }
+
+///
+///Dither amount 0..1 where 1 is a full step of TPDF noise.
+///
+    pub fn dither(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.dither = val;
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crusher::Crusher;
+    use crate::Effect;
+    use core::f32::consts::TAU;
+
+    #[test]
+    fn crush() {
+        let mut cr = Crusher::default();
+        cr.bits(2.0).downsample(2.5);
+
+        let mut changes = 0;
+        let mut last = 0.0;
+        for n in 0..1000 {
+            let y = cr.process((n as f32 * 0.01).sin() * 0.9);
+            assert!([-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].iter().any(|l| (y - l).abs() < 1e-6));
+            if y != last { changes += 1; last = y; }
+        }
+        assert!(changes > 0 && changes <= 400);
+
+//Fractional depths sit between whole ones.
+        assert!(cr.set_param(0, 3.5).is_ok());
+        assert!(cr.get_param(0).ok() == Some(3.5));
+    }
+
+    #[test]
+    fn levels() {
+//1 bit gives exactly two output levels.
+        let mut cr = Crusher::default();
+        cr.bits(1.0).downsample(1.0);
+        let mut levels: Vec<f32> = Vec::new();
+        for n in 0..1000 {
+            let y = cr.process((n as f32 * 0.01).sin() * 0.9);
+            if !levels.contains(&y) {
+                levels.push(y);
+            }
+        }
+        assert!(levels.len() == 2);
+        assert!(levels.contains(&-1.0) && levels.contains(&1.0));
+    }
+
+    #[test]
+    fn fractional() {
+//Fractional depths still reach -1 and 1 and quantize symmetrically.
+        for &bits in [1.5, 2.5, 3.3].iter() {
+            let mut cr = Crusher::default();
+            cr.bits(bits).downsample(1.0);
+            let mut neg = Crusher::default();
+            neg.bits(bits).downsample(1.0);
+
+//Ten whole cycles offset half a sample so no input sits on a tie at 0.
+            let (mut lo, mut hi, mut sum) = (0.0f32, 0.0f32, 0.0);
+            for n in 0..4800 {
+                let x = (TAU * (n as f32 + 0.5) / 480.0).sin();
+                let y = cr.process(x);
+                assert!((neg.process(-x) + y).abs() < 1e-6);
+                lo = lo.min(y);
+                hi = hi.max(y);
+                sum += y;
+            }
+            assert!(lo == -1.0 && hi == 1.0);
+            assert!((sum / 4800.0).abs() < 0.01);
+        }
+    }
+}
@@ -22,14 +22,27 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use super::{SAMPLE_RATE, SAMPLE_RATE_USIZE, SampleType};
-use common::offset::{Offset};
+use super::{SAMPLE_RATE, SampleType};
 use crate::Effect;
-use peripherals::debug;
+use crate::delayline::{DelayLine, Interp};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
 
 const DELAY_SECONDS: usize = 2;
-const DELAY_MAX:     usize = (SAMPLE_RATE_USIZE * DELAY_SECONDS);
-const DELAY_BUF_SZ:  usize = DELAY_MAX * 2;
+
+///
+///Time constant of the glide to a new delay time.
+///
+const GLIDE_SECONDS: SampleType = 0.02;
+
+static PARAMS: [Desc; 5] = [
+    Desc { name: "delay",    min: 0.0, max: DELAY_SECONDS as SampleType, default: DELAY_SECONDS as SampleType, unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "feedback", min: 0.0, max: 1.0, default: 0.5, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "wet",      min: 0.0, max: 1.0, default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "dry",      min: 0.0, max: 1.0, default: 0.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "interp",   min: 0.0, max: 2.0, default: 1.0, unit: Unit::None,  curve: Curve::Stepped },
+];
 
 
 /***********************************************************************
@@ -37,27 +50,33 @@ const DELAY_BUF_SZ:  usize = DELAY_MAX * 2;
  **********************************************************************/
 
 pub struct Delay {
-    pub delay:    usize,      //Delay in samples.
-    pub feedback: SampleType, //Feedback.
-    pub wet:      SampleType, //Wet (delayed) signal.
-    pub dry:      SampleType, //Dry (input) signal.
-
-    rd:  Offset,     //Read pointer.
-    wr:  Offset,     //Write pointer.
-    buf: [SampleType; DELAY_BUF_SZ], //Delay buffer.
+    rate:     SampleType, //Sample rate.
+    time:     Smooth,     //Delay in samples. Glides to new values.
+    feedback: Smooth,     //Feedback.
+    wet:      Smooth,     //Wet (delayed) signal.
+    dry:      Smooth,     //Dry (input) signal.
+    interp:   Interp,     //Read interpolation.
+    line:     DelayLine,  //Delay buffer.
 }
 
 impl Default for Delay {
     fn default() -> Self {
+        let max = delay_max(SAMPLE_RATE);
+
+        let mut time = Smooth::new(max as SampleType);
+        time.time(GLIDE_SECONDS);
+
+        let mut line = DelayLine::new(max);
+        line.interp(Interp::Hermite);
+
         Delay {
-            delay: DELAY_MAX,
-            feedback: 0.25,
-            wet: 1.0,
-            dry: 0.0,
-
-            rd: Offset(0),
-            wr: Offset(DELAY_MAX - 1),
-            buf: [SampleType::default(); DELAY_BUF_SZ]
+            rate: SAMPLE_RATE,
+            time,
+            feedback: Smooth::new(0.5),
+            wet: Smooth::new(1.0),
+            dry: Smooth::new(0.0),
+            interp: Interp::Hermite,
+            line,
         }
     }
 }
@@ -67,88 +86,116 @@ impl Effect for Delay {
 ///Process.
 ///
     fn process(&mut self, smpl_in: SampleType) -> SampleType {
-        let smpl_rd  = self.buf[self.rd.0];
-        let smpl_wr  = self.buf[self.wr.0];
-        let smpl_out = (smpl_in * self.dry) + (smpl_rd * self.wet);
-
-        self.buf[self.wr.0] = smpl_in + (smpl_wr * self.feedback);
-
-        self.wr.inc(DELAY_BUF_SZ);
-        self.rd.inc(DELAY_BUF_SZ);
-
-//         debug::out("smpl_in = ");
-//         debug::u32hex(smpl_in as u32);
-//         debug::out("\r\n");
-//         debug::out("smpl_out = ");
-//         debug::u32hex(smpl_out as u32);
-//         debug::out("\r\n");
+        self.tick(smpl_in)
+    }
 
-        return smpl_out;
+///
+///Process block.
+///
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
     }
 
 ///
 ///Reset delay to defaults.
 ///
     fn reset(&mut self) {
-        self.delay = DELAY_MAX;
-        self.feedback = 0.5;
-        self.wet = 1.0;
-        self.dry = 0.0;
-
-        self.rd = Offset(0);
-        self.wr = Offset(DELAY_MAX - 1);
-        self.buf = [SampleType::default(); DELAY_BUF_SZ];
+        self.time.reset(self.line.max() as SampleType);
+        self.feedback.reset(0.5);
+        self.wet.reset(1.0);
+        self.dry.reset(0.0);
+        self.interp(1.0);
+        self.line.clear();
     }
 
-    fn num_params(&mut self) -> usize { 4 }
+///
+///Resize buffer for the new sample rate. Delay time in seconds is kept.
+///Buffer contents are cleared.
+///
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        let sec = self.time.target() / self.rate;
+
+        self.rate = sample_rate;
+        self.line.resize(delay_max(sample_rate));
 
-    fn set_param(&mut self, idx: usize, val: SampleType) {
+        self.time.sample_rate(sample_rate);
+        self.feedback.sample_rate(sample_rate);
+        self.wet.sample_rate(sample_rate);
+        self.dry.sample_rate(sample_rate);
+
+        self.time.reset(sec * sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
         match idx {
             0 =>  { self.delay(val); },
             1 =>  { self.feedback(val); },
             2 =>  { self.wet(val); },
             3 =>  { self.dry(val); },
-            _ => { panic!("Parameter doesn't exist.") }
+            4 =>  { self.interp(val); },
+            _ => { return Err(ERROR::INDEX) }
         }
+        Ok(())
     }
 
-    fn get_param(&mut self, idx: usize) -> SampleType { 
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> { 
         match idx {
-            0 =>  self.delay as SampleType,
-            1 =>  self.feedback,
-            2 =>  self.wet,
-            3 =>  self.dry,
-            _ => { panic!("Parameter doesn't exist.") }
+            0 =>  Ok(self.time.target() / self.rate),
+            1 =>  Ok(self.feedback.target()),
+            2 =>  Ok(self.wet.target()),
+            3 =>  Ok(self.dry.target()),
+            4 =>  Ok(match self.interp {
+                      Interp::Linear  => 0.0,
+                      Interp::Hermite => 1.0,
+                      Interp::Allpass => 2.0,
+                  }),
+            _ => Err(ERROR::INDEX)
         }
     }
 }
 
 impl Delay {
 ///
-///Write position is always "delay" samples ahead of read.
+///Process one sample. Read is fractional so the delay time can glide 
+///or be modulated without jumps.
 ///
     #[inline]
-    fn update_wr_pos(&mut self) {
-        self.wr = *Offset(self.rd.val()).add(self.delay).wrap(DELAY_BUF_SZ);
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let smpl_rd  = self.line.read(self.time.next());
+        let smpl_out = (smpl_in * self.dry.next()) + (smpl_rd * self.wet.next());
+
+        self.line.write(smpl_in + (smpl_rd * self.feedback.next()));
+
+//         debug::out("smpl_in = ");
+//         debug::u32hex(smpl_in as u32);
+//         debug::out("\r\n");
+//         debug::out("smpl_out = ");
+//         debug::u32hex(smpl_out as u32);
+//         debug::out("\r\n");
+
+        smpl_out
     }
 
 ///
-///Set delay in seconds. Delay is rounded to the nearest whole 
-///sample divisible by two.
+///Set delay in seconds. Delay time glides from the current value to 
+///the new one.
 ///
     pub fn delay(&mut self, sec: SampleType) -> &mut Self {
-        let mut new = self;
+        let new = self;
+        let max = new.line.max() as SampleType;
+        let delay = sec * new.rate;
 
-//Must be an integer multiple of 2.
-        let delay = ((((sec * SAMPLE_RATE) + 0.5) as usize) / 2) * 2;
-        if delay > DELAY_MAX {
-            new.delay = DELAY_MAX;
+        if delay > max {
+            new.time.set(max);
         } else {
-            new.delay = delay;
+            new.time.set(delay);
         }
 
-        new.update_wr_pos();
-        
         new
     }
 
@@ -156,8 +203,8 @@ impl Delay {
 ///Feedback in percent.
 ///
     pub fn feedback(&mut self, per: SampleType) -> &mut Self {
-        let mut new = self;
-        new.feedback = per;
+        let new = self;
+        new.feedback.set(per);
         new
     }
 
@@ -165,8 +212,8 @@ impl Delay {
 ///wet signal in percent.
 ///
     pub fn wet(&mut self, per: SampleType) -> &mut Self {
-        let mut new = self;
-        new.wet = per;
+        let new = self;
+        new.wet.set(per);
         new
     }
 
@@ -174,8 +221,30 @@ impl Delay {
 ///dry signal in percent.
 ///
     pub fn dry(&mut self, per: SampleType) -> &mut Self {
-        let mut new = self;
-        new.dry = per;
+        let new = self;
+        new.dry.set(per);
         new
     }
+
+///
+///Interpolation. 0 = linear, 1 = Hermite, 2 = allpass.
+///
+    pub fn interp(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.interp = match (val + 0.5) as usize {
+            0 => Interp::Linear,
+            1 => Interp::Hermite,
+            _ => Interp::Allpass,
+        };
+        new.line.interp(new.interp);
+        new
+    }
+}
+
+///
+///Maximum delay in samples at sample rate.
+///
+#[inline]
+fn delay_max(rate: SampleType) -> usize {
+    (rate as usize) * DELAY_SECONDS
 }
@@ -0,0 +1,277 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use alloc::vec;
+use alloc::vec::Vec;
+
+///
+///Extra samples kept past the maximum delay for interpolation.
+///
+const GUARD: usize = 4;
+
+/***********************************************************************
+ * Interp
+ **********************************************************************/
+
+///
+///Method used to read between samples.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interp {
+    Linear,  //Straight line between the two nearest samples.
+    Hermite, //4 point, 3rd order Hermite. Smooth, good for modulation.
+    Allpass, //1st order allpass. Flat response, best for fixed delays.
+}
+
+/***********************************************************************
+ * DelayLine
+ **********************************************************************/
+
+///
+///Circular buffer that can be read at fractional delays. 
+///
+///Write one sample per sample period with write(). Read before writing
+///with read(delay) to get the sample written "delay" samples ago where
+///a delay of 1.0 is the previously written sample. The allpass method
+///keeps state so should only be used for one read per sample.
+///
+pub struct DelayLine {
+    buf:    Vec<SampleType>, //Samples. Length is a power of two.
+    mask:   usize,           //Length - 1 for wrapping indexes.
+    wr:     usize,           //Next write position.
+    max:    usize,           //Longest delay in samples.
+    interp: Interp,          //Read method.
+    ap:     SampleType,      //Allpass interpolator output memory.
+}
+
+impl Default for DelayLine {
+    fn default() -> Self {
+        DelayLine::new(0)
+    }
+}
+
+impl DelayLine {
+///
+///New delay line able to delay up to max samples.
+///
+    pub fn new(max: usize) -> Self {
+        let len = (max + GUARD).next_power_of_two();
+        DelayLine {
+            buf:    vec![SampleType::default(); len],
+            mask:   len - 1,
+            wr:     0,
+            max,
+            interp: Interp::Linear,
+            ap:     SampleType::default(),
+        }
+    }
+
+///
+///Reallocate for a new maximum delay. Contents are cleared.
+///
+    pub fn resize(&mut self, max: usize) {
+        let interp = self.interp;
+        *self = DelayLine::new(max);
+        self.interp = interp;
+    }
+
+///
+///Zero contents.
+///
+    pub fn clear(&mut self) {
+        for smpl in self.buf.iter_mut() {
+            *smpl = SampleType::default();
+        }
+        self.ap = SampleType::default();
+    }
+
+///
+///Set read interpolation method.
+///
+    pub fn interp(&mut self, interp: Interp) -> &mut Self {
+        let new = self;
+        new.interp = interp;
+        new
+    }
+
+///
+///Longest delay in samples.
+///
+    #[inline]
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+///
+///Add a sample.
+///
+    #[inline]
+    pub fn write(&mut self, smpl: SampleType) {
+        self.buf[self.wr] = smpl;
+        self.wr = (self.wr + 1) & self.mask;
+    }
+
+///
+///Sample written a whole number of samples ago. 1 is the most recent.
+///
+    #[inline]
+    pub fn tap(&self, delay: usize) -> SampleType {
+        self.buf[(self.wr + self.buf.len() - delay) & self.mask]
+    }
+
+///
+///Sample written delay samples ago, interpolated. Delay is limited to
+///1..max (2..max for Hermite which needs a sample on either side and
+///1.5..max for Allpass which rings as its delay nears 1).
+///
+    #[inline]
+    pub fn read(&mut self, delay: SampleType) -> SampleType {
+        let min = match self.interp {
+            Interp::Hermite => 2.0,
+            Interp::Allpass => 1.5,
+            Interp::Linear  => 1.0,
+        };
+        let max = self.max as SampleType;
+        let delay = if delay < min { min } else if delay > max { max } else { delay };
+
+        let mut idx  = delay as usize;
+        let mut frac = delay - (idx as SampleType);
+
+        match self.interp {
+            Interp::Linear => {
+                let x0 = self.tap(idx);
+                let x1 = self.tap(idx + 1);
+                x0 + ((x1 - x0) * frac)
+            },
+
+            Interp::Hermite => {
+                let xm1 = self.tap(idx - 1);
+                let x0  = self.tap(idx);
+                let x1  = self.tap(idx + 1);
+                let x2  = self.tap(idx + 2);
+
+                let c1 = 0.5 * (x1 - xm1);
+                let c2 = xm1 - (2.5 * x0) + (2.0 * x1) - (0.5 * x2);
+                let c3 = (0.5 * (x2 - xm1)) + (1.5 * (x0 - x1));
+
+                (((c3 * frac) + c2) * frac + c1) * frac + x0
+            },
+
+            Interp::Allpass => {
+//Keep the allpass delay in 0.5..1.5 so the coefficient stays small.
+                if frac < 0.5 && idx > 1 {
+                    idx  -= 1;
+                    frac += 1.0;
+                }
+
+                let eta = (1.0 - frac) / (1.0 + frac);
+                let x0  = self.tap(idx);
+                let x1  = self.tap(idx + 1);
+
+                self.ap = (eta * (x0 - self.ap)) + x1;
+                self.ap
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::delayline::{DelayLine, Interp};
+    use crate::SampleType;
+
+    fn ramp(line: &mut DelayLine, len: usize) {
+        for i in 0..len {
+            line.write(i as SampleType);
+        }
+    }
+
+    #[test]
+    fn whole() {
+        let mut line = DelayLine::new(100);
+        ramp(&mut line, 200);
+        for d in 1..100 {
+            assert!(line.tap(d) == (200 - d) as SampleType);
+            assert!(line.read(d as SampleType) == (200 - d) as SampleType);
+        }
+    }
+
+    #[test]
+    fn fractional() {
+//Linear and Hermite both reproduce a ramp exactly.
+        for interp in [Interp::Linear, Interp::Hermite].iter() {
+            let mut line = DelayLine::new(100);
+            line.interp(*interp);
+            ramp(&mut line, 200);
+            for d in 4..90 {
+                let d = (d as SampleType) + 0.25;
+                let v = line.read(d);
+                assert!((v - (200.0 - d)).abs() < 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn allpass() {
+//Constant input comes out unchanged once the allpass settles.
+        let mut line = DelayLine::new(100);
+        line.interp(Interp::Allpass);
+        for _ in 0..200 {
+            line.write(1.0);
+        }
+        let mut v = 0.0;
+        for _ in 0..50 {
+            v = line.read(10.3);
+        }
+        assert!((v - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn allpass_short() {
+//An impulse read at 1.1 samples dies away instead of ringing.
+        let mut line = DelayLine::new(100);
+        line.interp(Interp::Allpass);
+        line.write(1.0);
+        line.read(1.1);
+        for _ in 0..20 {
+            line.write(0.0);
+            line.read(1.1);
+        }
+        let mut tail = 0.0;
+        for _ in 0..20 {
+            line.write(0.0);
+            tail += line.read(1.1).abs();
+        }
+        assert!(tail < 1e-6);
+    }
+
+    #[test]
+    fn limits() {
+        let mut line = DelayLine::new(10);
+        ramp(&mut line, 100);
+        assert!(line.read(0.0) == 99.0);
+        assert!(line.read(1000.0) == 90.0);
+    }
+}
@@ -0,0 +1,178 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use libm::{expf, fabsf, sqrtf};
+
+/***********************************************************************
+ * Detector
+ **********************************************************************/
+
+///
+///Level detection. Peak follows the rectified signal, RMS follows its
+///square.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Peak,
+    Rms,
+}
+
+///
+///Envelope follower with separate attack and release times. Shared by
+///the dynamics effects.
+///
+pub struct Detector {
+    mode:    Mode,
+    env:     SampleType, //Current level (squared level for RMS).
+    attack:  SampleType, //Attack time in seconds.
+    release: SampleType, //Release time in seconds.
+    att:     SampleType, //Attack coefficient.
+    rel:     SampleType, //Release coefficient.
+    rate:    SampleType, //Sample rate.
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        let mut det = Detector {
+            mode:    Mode::Peak,
+            env:     0.0,
+            attack:  0.01,
+            release: 0.1,
+            att:     0.0,
+            rel:     0.0,
+            rate:    SAMPLE_RATE,
+        };
+        det.update();
+        det
+    }
+}
+
+///
+///One pole coefficient reaching ~63% of a step in sec seconds.
+///
+#[inline]
+fn coef(sec: SampleType, rate: SampleType) -> SampleType {
+    if sec > 0.0 {
+        expf(-1.0 / (sec * rate))
+    } else {
+        0.0
+    }
+}
+
+impl Detector {
+    fn update(&mut self) {
+        self.att = coef(self.attack, self.rate);
+        self.rel = coef(self.release, self.rate);
+    }
+
+    pub fn mode(&mut self, mode: Mode) -> &mut Self {
+        let new = self;
+        new.mode = mode;
+        new.env  = 0.0;
+        new
+    }
+
+///
+///Attack time in seconds. 0 follows rising levels immediately.
+///
+    pub fn attack(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.attack = sec;
+        new.att = coef(sec, new.rate);
+        new
+    }
+
+///
+///Release time in seconds. 0 follows falling levels immediately.
+///
+    pub fn release(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.release = sec;
+        new.rel = coef(sec, new.rate);
+        new
+    }
+
+    pub fn sample_rate(&mut self, rate: SampleType) -> &mut Self {
+        let new = self;
+        new.rate = rate;
+        new.update();
+        new
+    }
+
+    pub fn reset(&mut self) {
+        self.env = 0.0;
+    }
+
+///
+///Detected level as linear amplitude.
+///
+    pub fn level(&self) -> SampleType {
+        match self.mode {
+            Mode::Peak => self.env,
+            Mode::Rms  => sqrtf(self.env),
+        }
+    }
+
+///
+///Follow one sample and return the detected level.
+///
+    #[inline]
+    pub fn next(&mut self, smpl: SampleType) -> SampleType {
+        let x = match self.mode {
+            Mode::Peak => fabsf(smpl),
+            Mode::Rms  => smpl * smpl,
+        };
+
+        let c = if x > self.env { self.att } else { self.rel };
+        self.env = x + (c * (self.env - x));
+
+        if !self.env.is_finite() {
+            self.env = 0.0;
+        }
+
+        self.level()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::detector::{Detector, Mode};
+    use crate::SAMPLE_RATE;
+
+    #[test]
+    fn levels() {
+        let mut det = Detector::default();
+        det.attack(0.0).release(0.0);
+        assert!(det.next(-0.5) == 0.5);
+
+//Square wave RMS equals its amplitude.
+        det.mode(Mode::Rms).attack(0.01).release(0.01);
+        let mut lvl = 0.0;
+        for i in 0..(SAMPLE_RATE as usize) {
+            lvl = det.next(if i % 2 == 0 { 0.5 } else { -0.5 });
+        }
+        assert!((lvl - 0.5).abs() < 0.001);
+    }
+}
@@ -0,0 +1,410 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::oversample::Oversample;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::{expf, fabsf, fmodf, powf, tanhf};
+use core::f32::consts::PI;
+
+///
+///DC blocker corner in Hz. Removes the offset left by bias and the
+///asymmetric curves.
+///
+const DC_HZ: SampleType = 10.0;
+
+static PARAMS: [Desc; 7] = [
+    Desc { name: "curve",      min: 0.0,   max: 4.0,     default: 0.0,    unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "drive",      min: 0.0,   max: 48.0,    default: 12.0,   unit: Unit::Db,   curve: Curve::Linear },
+    Desc { name: "tone",       min: 500.0, max: 20000.0, default: 6000.0, unit: Unit::Hz,   curve: Curve::Log },
+    Desc { name: "level",      min: -48.0, max: 12.0,    default: -6.0,   unit: Unit::Db,   curve: Curve::Linear },
+    Desc { name: "bias",       min: -1.0,  max: 1.0,     default: 0.0,    unit: Unit::None, curve: Curve::Linear },
+    Desc { name: "oversample", min: 1.0,   max: 8.0,     default: 4.0,    unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "mix",        min: 0.0,   max: 1.0,     default: 1.0,    unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Transfer
+ **********************************************************************/
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transfer {
+    SoftClip, //tanh.
+    HardClip, //Clamp to -1..1.
+    Tube,     //Asymmetric, softer on the negative side.
+    Foldback, //Folds back from -1..1 as a triangle.
+    Fuzz,     //Steep exponential, nearly square when driven.
+}
+
+impl Transfer {
+    pub fn from_param(val: SampleType) -> Transfer {
+        match (val + 0.5) as usize {
+            0 => Transfer::SoftClip,
+            1 => Transfer::HardClip,
+            2 => Transfer::Tube,
+            3 => Transfer::Foldback,
+            _ => Transfer::Fuzz,
+        }
+    }
+
+    pub fn to_param(&self) -> SampleType {
+        match self {
+            Transfer::SoftClip => 0.0,
+            Transfer::HardClip => 1.0,
+            Transfer::Tube     => 2.0,
+            Transfer::Foldback => 3.0,
+            Transfer::Fuzz     => 4.0,
+        }
+    }
+
+    #[inline]
+    pub fn apply(&self, x: SampleType) -> SampleType {
+        match self {
+            Transfer::SoftClip => tanhf(x),
+            Transfer::HardClip => x.clamp(-1.0, 1.0),
+            Transfer::Tube     => if x >= 0.0 { tanhf(x) } else { expf(x) - 1.0 },
+            Transfer::Foldback => 1.0 - fabsf(fmodf(fmodf(x + 1.0, 4.0) + 4.0, 4.0) - 2.0),
+            Transfer::Fuzz     => {
+                let y = 1.0 - expf(-4.0 * fabsf(x));
+                if x < 0.0 { -y } else { y }
+            },
+        }
+    }
+}
+
+/***********************************************************************
+ * Shaper
+ **********************************************************************/
+
+///
+///The nonlinearity alone, run oversampled.
+///
+struct Shaper {
+    transfer: Transfer,
+}
+
+impl Effect for Shaper {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.transfer.apply(smpl_in)
+    }
+}
+
+/***********************************************************************
+ * Distortion
+ **********************************************************************/
+
+///
+///Waveshaping distortion. Drive and bias are applied before the curve,
+///which runs oversampled to keep aliasing down. A DC blocker, one pole
+///tone lowpass and output level follow at the normal rate. Mix blends
+///the result with the dry input.
+///
+pub struct Distortion {
+    drive_db: SampleType,
+    drive:    Smooth,     //Input gain as linear.
+    tone:     SampleType, //Lowpass corner in Hz.
+    level_db: SampleType,
+    level:    Smooth,     //Output gain as linear.
+    bias:     Smooth,     //Offset before the curve.
+    mix:      Smooth,     //0 dry .. 1 distorted.
+    rate:     SampleType, //Sample rate.
+
+    shaper:   Oversample<Shaper>,
+    lp:       SampleType, //Tone coefficient.
+    lp_z:     SampleType,
+    dc:       SampleType, //DC blocker coefficient.
+    dc_x:     SampleType,
+    dc_y:     SampleType,
+}
+
+impl Default for Distortion {
+    fn default() -> Self {
+        let mut dist = Distortion {
+            drive_db: 12.0,
+            drive:    Smooth::new(1.0),
+            tone:     6000.0,
+            level_db: -6.0,
+            level:    Smooth::new(1.0),
+            bias:     Smooth::new(0.0),
+            mix:      Smooth::new(1.0),
+            rate:     SAMPLE_RATE,
+            shaper:   Oversample::new(Shaper { transfer: Transfer::SoftClip }, 4),
+            lp:       0.0,
+            lp_z:     0.0,
+            dc:       0.0,
+            dc_x:     0.0,
+            dc_y:     0.0,
+        };
+        dist.prepare(SAMPLE_RATE, 0);
+        dist.reset();
+        dist
+    }
+}
+
+#[inline]
+fn db_to_gain(db: SampleType) -> SampleType {
+    powf(10.0, db / 20.0)
+}
+
+impl Effect for Distortion {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.transfer(Transfer::SoftClip);
+        self.drive(12.0);
+        self.drive.reset(db_to_gain(12.0));
+        self.tone(6000.0);
+        self.level(-6.0);
+        self.level.reset(db_to_gain(-6.0));
+        self.bias.reset(0.0);
+        self.mix.reset(1.0);
+        self.shaper.factor(4);
+        self.shaper.reset();
+        self.lp_z = 0.0;
+        self.dc_x = 0.0;
+        self.dc_y = 0.0;
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, max_block: usize) {
+        self.rate = sample_rate;
+        self.drive.sample_rate(sample_rate);
+        self.level.sample_rate(sample_rate);
+        self.bias.sample_rate(sample_rate);
+        self.mix.sample_rate(sample_rate);
+        self.shaper.prepare(sample_rate, max_block);
+        self.dc = 1.0 - (2.0 * PI * DC_HZ / sample_rate);
+        let tone = self.tone;
+        self.tone(tone);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.transfer(Transfer::from_param(val)); },
+            1 => { self.drive(val); },
+            2 => { self.tone(val); },
+            3 => { self.level(val); },
+            4 => { self.bias(val); },
+            5 => { self.oversample((val + 0.5) as usize); },
+            6 => { self.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.shaper.inner().transfer.to_param()),
+            1 => Ok(self.drive_db),
+            2 => Ok(self.tone),
+            3 => Ok(self.level_db),
+            4 => Ok(self.bias.target()),
+            5 => Ok(self.shaper.get_factor() as SampleType),
+            6 => Ok(self.mix.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Distortion {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let smpl = (smpl_in * self.drive.next()) + self.bias.next();
+        let smpl = self.shaper.process(smpl);
+
+        self.dc_y = smpl - self.dc_x + (self.dc * self.dc_y);
+        self.dc_x = smpl;
+
+        self.lp_z = self.dc_y + (self.lp * (self.lp_z - self.dc_y));
+
+        let mix = self.mix.next();
+        (smpl_in * (1.0 - mix)) + (self.lp_z * self.level.next() * mix)
+    }
+
+    pub fn transfer(&mut self, transfer: Transfer) -> &mut Self {
+        let new = self;
+        new.shaper.inner().transfer = transfer;
+        new
+    }
+
+///
+///Input gain in dB.
+///
+    pub fn drive(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.drive_db = db;
+        new.drive.set(db_to_gain(db));
+        new
+    }
+
+///
+///Tone lowpass corner in Hz.
+///
+    pub fn tone(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        let max = new.rate * 0.45;
+        new.tone = hz;
+        new.lp = expf(-2.0 * PI * (if hz > max { max } else { hz }) / new.rate);
+        new
+    }
+
+///
+///Output gain in dB.
+///
+    pub fn level(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.level_db = db;
+        new.level.set(db_to_gain(db));
+        new
+    }
+
+    pub fn bias(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.bias.set(val);
+        new
+    }
+
+///
+///Oversampling factor 1, 2, 4 or 8.
+///
+    pub fn oversample(&mut self, factor: usize) -> &mut Self {
+        let new = self;
+        new.shaper.factor(factor);
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distortion::{Distortion, Transfer};
+    use crate::{Effect, SAMPLE_RATE, SampleType};
+
+    const LEN: usize = 4800;
+
+    fn sine(freq: SampleType, amp: SampleType) -> Vec<SampleType> {
+        (0..LEN).map(|n| {
+            amp * (2.0 * std::f32::consts::PI * freq * (n as SampleType) / SAMPLE_RATE).sin()
+        }).collect()
+    }
+
+    fn run(dist: &mut Distortion, input: &[SampleType]) -> Vec<SampleType> {
+        let mut output = vec![0.0; input.len()];
+        for _ in 0..4 {
+            dist.process_block(input, &mut output);
+        }
+        output
+    }
+
+///
+///Magnitude at freq. LEN covers whole cycles of every frequency used.
+///
+    fn magnitude(smpls: &[SampleType], freq: SampleType) -> SampleType {
+        let w = 2.0 * std::f32::consts::PI * freq / SAMPLE_RATE;
+        let (re, im) = smpls.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, x)| {
+            (re + x * (w * n as SampleType).cos(), im - x * (w * n as SampleType).sin())
+        });
+        (re * re + im * im).sqrt() / (LEN as SampleType)
+    }
+
+    #[test]
+    fn curves() {
+        for &t in [Transfer::SoftClip, Transfer::HardClip, Transfer::Tube, Transfer::Foldback, Transfer::Fuzz].iter() {
+            assert!(t.apply(0.0).abs() < 1e-6);
+            for i in -1000..1000 {
+                let y = t.apply(i as f32 * 0.01);
+                assert!((-1.0..=1.0).contains(&y));
+            }
+        }
+        assert!((Transfer::Foldback.apply(1.5) - 0.5).abs() < 1e-6);
+        assert!((Transfer::Foldback.apply(-3.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mix() {
+        let input = sine(1000.0, 0.5);
+        let mut dist = Distortion::default();
+        dist.mix(0.0);
+        let output = run(&mut dist, &input);
+        assert!(output.iter().zip(input.iter()).all(|(y, x)| y == x));
+    }
+
+    #[test]
+    fn drive_tone() {
+//More drive squashes a sine towards a square, raising its rms.
+        let rms = |smpls: &[SampleType]| {
+            (smpls.iter().map(|x| x * x).sum::<SampleType>() / (smpls.len() as SampleType)).sqrt()
+        };
+        let input = sine(200.0, 0.5);
+        let mut dist = Distortion::default();
+        dist.drive(0.0).level(0.0);
+        let clean = run(&mut dist, &input);
+        dist.drive(36.0);
+        let driven = run(&mut dist, &input);
+        assert!(rms(&driven) > 1.5 * rms(&clean));
+
+//Closing the tone control takes the top end off.
+        let input = sine(8000.0, 0.5);
+        let mut dist = Distortion::default();
+        dist.drive(0.0).level(0.0).tone(20000.0);
+        let open = run(&mut dist, &input);
+        dist.tone(500.0);
+        let closed = run(&mut dist, &input);
+        assert!(rms(&closed) < 0.2 * rms(&open));
+    }
+
+    #[test]
+    fn aliasing() {
+//Hard clipped 10kHz has harmonics at 30k, 50k, 70k, 90k... which fold
+//back to 18k, 2k, 22k, 6k. Oversampling should keep them down.
+        let input = sine(10000.0, 0.9);
+        let alias = |factor: usize| {
+            let mut dist = Distortion::default();
+            dist.transfer(Transfer::HardClip).drive(24.0).level(-24.0).tone(20000.0).oversample(factor);
+            let output = run(&mut dist, &input);
+            [2000.0, 6000.0, 18000.0, 22000.0].iter().map(|f| magnitude(&output, *f)).sum::<SampleType>()
+        };
+        let plain = alias(1);
+        let over  = alias(4);
+        assert!(over < 0.25 * plain);
+    }
+}
@@ -0,0 +1,457 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::{expf, logf};
+
+///
+///Exponential segments aim past their end point by these ratios so 
+///they arrive in finite time. Attack overshoots a lot for the convex 
+///analog shape, decay and release hardly at all.
+///
+const RATIO_ATTACK: SampleType = 0.3;
+const RATIO_DECAY:  SampleType = 0.0001;
+
+static PARAMS: [Desc; 11] = [
+    Desc { name: "attack",    min: 0.0005,   max: 10.0,    default: 0.01, unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "decay",     min: 0.001,    max: 10.0,    default: 0.1,  unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "sustain",   min: 0.0,      max: 1.0,     default: 0.7,  unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "release",   min: 0.001,    max: 20.0,    default: 0.3,  unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "curve",     min: 0.0,      max: 1.0,     default: 1.0,  unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "mode",      min: 0.0,      max: 1.0,     default: 0.0,  unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "threshold", min: -1.0,     max: 1.0,     default: 0.5,  unit: Unit::None,    curve: Curve::Linear },
+    Desc { name: "port",      min: 0.0,      max: 1.0,     default: 0.0,  unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "depth",     min: 0.0,      max: 20000.0, default: 1.0,  unit: Unit::None,    curve: Curve::Linear },
+    Desc { name: "offset",    min: -20000.0, max: 20000.0, default: 0.0,  unit: Unit::None,    curve: Curve::Linear },
+    Desc { name: "retrig",    min: 0.0,      max: 1.0,     default: 0.0,  unit: Unit::None,    curve: Curve::Stepped },
+];
+
+/***********************************************************************
+ * Stage
+ **********************************************************************/
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/***********************************************************************
+ * Envelope
+ **********************************************************************/
+
+///
+///Envelope generator. The gate is high while its input is above 
+///threshold. Input comes from the process input (port 0) or, when port
+///is 1, from input port 1.
+///
+///In ADSR mode (0) the level rises to 1 over attack, falls to sustain 
+///over decay, holds while the gate stays high then falls to 0 over 
+///release. In AR mode (1) a rising gate fires attack straight into 
+///release no matter how long the gate is held, for one shot swells.
+///
+///Linear segments take their full time to cross the whole 0..1 range.
+///Exponential segments are shaped like an analog envelope and reach 
+///their end in about the same time. A new gate restarts the attack from
+///the current level unless retrig is on, in which case it starts from 0.
+///
+///Output is level times depth plus offset so it can drive parameter 
+///connections directly.
+///
+pub struct Envelope {
+    attack:    SampleType, //Seconds.
+    decay:     SampleType, //Seconds.
+    sustain:   SampleType, //Level 0..1.
+    release:   SampleType, //Seconds.
+    linear:    bool,       //Linear instead of exponential segments.
+    oneshot:   bool,       //AR mode.
+    threshold: SampleType, //Gate is high above this.
+    port:      bool,       //Gate from input port 1.
+    depth:     Smooth,     //Output is scaled by this amount.
+    offset:    Smooth,     //Then offset by this amount.
+    retrig:    bool,       //Restart attack from zero.
+    rate:      SampleType, //Sample rate.
+
+    stage:     Stage,
+    level:     SampleType, //Current level 0..1.
+    gate:      bool,       //Gate state last sample.
+    att:       SampleType, //Attack coefficient (exponential) or step (linear).
+    dec:       SampleType, //Decay coefficient or step.
+    rel:       SampleType, //Release coefficient or step.
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        let mut env = Envelope {
+            attack:    0.01,
+            decay:     0.1,
+            sustain:   0.7,
+            release:   0.3,
+            linear:    false,
+            oneshot:   false,
+            threshold: 0.5,
+            port:      false,
+            depth:     Smooth::new(1.0),
+            offset:    Smooth::new(0.0),
+            retrig:    false,
+            rate:      SAMPLE_RATE,
+            stage:     Stage::Idle,
+            level:     0.0,
+            gate:      false,
+            att:       0.0,
+            dec:       0.0,
+            rel:       0.0,
+        };
+        env.prepare(SAMPLE_RATE, 0);
+        env
+    }
+}
+
+///
+///Per sample coefficient for an exponential segment of sec seconds 
+///aiming ratio past its end.
+///
+#[inline]
+fn coef(sec: SampleType, ratio: SampleType, rate: SampleType) -> SampleType {
+    expf(-logf((1.0 + ratio) / ratio) / (sec * rate))
+}
+
+impl Effect for Envelope {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+
+///
+///Gate on port 0, or port 1 when port is 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let output = match outputs.first_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        match inputs {
+            [_, gate, ..] if self.port => self.process_block(gate, output),
+            [gate, ..] => self.process_block(gate, output),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.attack    = 0.01;
+        self.decay     = 0.1;
+        self.sustain   = 0.7;
+        self.release   = 0.3;
+        self.linear    = false;
+        self.oneshot   = false;
+        self.threshold = 0.5;
+        self.port      = false;
+        self.depth.reset(1.0);
+        self.offset.reset(0.0);
+        self.retrig    = false;
+        self.stage     = Stage::Idle;
+        self.level     = 0.0;
+        self.gate      = false;
+        self.update();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.depth.sample_rate(sample_rate);
+        self.offset.sample_rate(sample_rate);
+        self.update();
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0  => { self.attack(val); },
+            1  => { self.decay(val); },
+            2  => { self.sustain(val); },
+            3  => { self.release(val); },
+            4  => { self.linear(val < 0.5); },
+            5  => { self.oneshot(val >= 0.5); },
+            6  => { self.threshold(val); },
+            7  => { self.port(val >= 0.5); },
+            8  => { self.depth(val); },
+            9  => { self.offset(val); },
+            10 => { self.retrig(val >= 0.5); },
+            _  => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0  => Ok(self.attack),
+            1  => Ok(self.decay),
+            2  => Ok(self.sustain),
+            3  => Ok(self.release),
+            4  => Ok(if self.linear { 0.0 } else { 1.0 }),
+            5  => Ok(if self.oneshot { 1.0 } else { 0.0 }),
+            6  => Ok(self.threshold),
+            7  => Ok(if self.port { 1.0 } else { 0.0 }),
+            8  => Ok(self.depth.target()),
+            9  => Ok(self.offset.target()),
+            10 => Ok(if self.retrig { 1.0 } else { 0.0 }),
+            _  => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Envelope {
+///
+///Recompute segment coefficients after a time or curve change.
+///
+    fn update(&mut self) {
+        if self.linear {
+            self.att = 1.0 / (self.attack * self.rate);
+            self.dec = 1.0 / (self.decay * self.rate);
+            self.rel = 1.0 / (self.release * self.rate);
+        } else {
+            self.att = coef(self.attack, RATIO_ATTACK, self.rate);
+            self.dec = coef(self.decay, RATIO_DECAY, self.rate);
+            self.rel = coef(self.release, RATIO_DECAY, self.rate);
+        }
+    }
+
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let gate = smpl_in > self.threshold;
+
+        if gate && !self.gate {
+            if self.retrig {
+                self.level = 0.0;
+            }
+            self.stage = Stage::Attack;
+        } else if !gate && self.gate && !self.oneshot && self.stage != Stage::Idle {
+            self.stage = Stage::Release;
+        }
+        self.gate = gate;
+
+        match self.stage {
+            Stage::Idle => {},
+
+            Stage::Attack => {
+                self.level = if self.linear {
+                    self.level + self.att
+                } else {
+                    (1.0 + RATIO_ATTACK) + ((self.level - (1.0 + RATIO_ATTACK)) * self.att)
+                };
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = if self.oneshot { Stage::Release } else { Stage::Decay };
+                }
+            },
+
+            Stage::Decay => {
+                self.level = if self.linear {
+                    self.level - self.dec
+                } else {
+                    let tgt = self.sustain - RATIO_DECAY;
+                    tgt + ((self.level - tgt) * self.dec)
+                };
+                if self.level <= self.sustain {
+                    self.level = self.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            },
+
+//Follows sustain changes while held.
+            Stage::Sustain => {
+                self.level = self.sustain;
+            },
+
+            Stage::Release => {
+                self.level = if self.linear {
+                    self.level - self.rel
+                } else {
+                    -RATIO_DECAY + ((self.level + RATIO_DECAY) * self.rel)
+                };
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            },
+        }
+
+        (self.level * self.depth.next()) + self.offset.next()
+    }
+
+    #[inline]
+    pub fn level(&self) -> SampleType {
+        self.level
+    }
+
+    #[inline]
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    pub fn attack(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.attack = sec;
+        new.update();
+        new
+    }
+
+    pub fn decay(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.decay = sec;
+        new.update();
+        new
+    }
+
+    pub fn sustain(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.sustain = val;
+        new
+    }
+
+    pub fn release(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.release = sec;
+        new.update();
+        new
+    }
+
+///
+///Linear instead of exponential segments.
+///
+    pub fn linear(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.linear = on;
+        new.update();
+        new
+    }
+
+///
+///AR mode. Attack then release on every rising gate.
+///
+    pub fn oneshot(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.oneshot = on;
+        new
+    }
+
+    pub fn threshold(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.threshold = val;
+        new
+    }
+
+///
+///Read the gate from input port 1 instead of the process input.
+///
+    pub fn port(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.port = on;
+        new
+    }
+
+    pub fn depth(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.depth.set(val);
+        new
+    }
+
+    pub fn offset(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.offset.set(val);
+        new
+    }
+
+    pub fn retrig(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.retrig = on;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::envelope::{Envelope, Stage};
+    use crate::Effect;
+
+    #[test]
+    fn adsr() {
+        for linear in [true, false].iter() {
+            let mut env = Envelope::default();
+            env.linear(*linear).attack(0.01).decay(0.05).sustain(0.5).release(0.1);
+
+//Attack reaches the top in about 10ms.
+            for _ in 0..490 {
+                env.process(1.0);
+            }
+            assert!(env.level() > 0.95);
+
+            for _ in 0..24000 {
+                env.process(1.0);
+            }
+            assert!(env.stage() == Stage::Sustain);
+            assert!((env.level() - 0.5).abs() < 1e-6);
+
+            for _ in 0..4800 {
+                env.process(0.0);
+            }
+            assert!(env.stage() == Stage::Idle);
+            assert!(env.level() == 0.0);
+        }
+    }
+
+    #[test]
+    fn oneshot() {
+//A single sample trigger plays the whole swell.
+        let mut env = Envelope::default();
+        env.oneshot(true).attack(0.01).release(0.01);
+        env.process(1.0);
+        let mut peak = 0.0;
+        for _ in 0..4800 {
+            let val = env.process(0.0);
+            peak = if val > peak { val } else { peak };
+        }
+        assert!(peak == 1.0);
+        assert!(env.stage() == Stage::Idle);
+    }
+}
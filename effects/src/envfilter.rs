@@ -0,0 +1,298 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::detector::{Detector, Mode};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::svf::Filter;
+use libm::exp2f;
+
+static PARAMS: [Desc; 10] = [
+    Desc { name: "sensitivity", min: 0.0,    max: 20.0,   default: 4.0,   unit: Unit::None,    curve: Curve::Linear },
+    Desc { name: "attack",      min: 0.0001, max: 0.5,    default: 0.005, unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "release",     min: 0.005,  max: 2.0,    default: 0.1,   unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "freq",        min: 50.0,   max: 2000.0, default: 250.0, unit: Unit::Hz,      curve: Curve::Log },
+    Desc { name: "range",       min: 0.0,    max: 6.0,    default: 4.0,   unit: Unit::None,    curve: Curve::Linear },
+    Desc { name: "q",           min: 0.5,    max: 20.0,   default: 5.0,   unit: Unit::None,    curve: Curve::Log },
+    Desc { name: "direction",   min: 0.0,    max: 1.0,    default: 0.0,   unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "mode",        min: 0.0,    max: 1.0,    default: 0.0,   unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "pedal",       min: 0.0,    max: 1.0,    default: 0.0,   unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "mix",         min: 0.0,    max: 1.0,    default: 1.0,   unit: Unit::Ratio,   curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * EnvelopeFilter
+ **********************************************************************/
+
+///
+///Auto-wah. The input level times sensitivity, plus the pedal position,
+///gives a sweep position 0..1 that moves a resonant state variable 
+///filter up (or down) range octaves from freq.
+///
+///With sensitivity 0 only the pedal moves the filter, so a rack 
+///connection from an expression input makes a classic wah.
+///
+pub struct EnvelopeFilter {
+    sensitivity: SampleType,
+    attack:      SampleType, //Seconds.
+    release:     SampleType, //Seconds.
+    freq:        Smooth,     //Sweep start in Hz.
+    range:       SampleType, //Sweep in octaves.
+    q:           Smooth,
+    down:        bool,       //Sweep down from the top instead of up.
+    bandpass:    bool,       //Band pass instead of low pass.
+    pedal:       Smooth,     //Manual position 0..1.
+    mix:         Smooth,
+    rate:        SampleType, //Sample rate.
+
+    detector:    Detector,
+    filter:      Filter,
+}
+
+impl Default for EnvelopeFilter {
+    fn default() -> Self {
+        let mut ef = EnvelopeFilter {
+            sensitivity: 4.0,
+            attack:      0.005,
+            release:     0.1,
+            freq:        Smooth::new(250.0),
+            range:       4.0,
+            q:           Smooth::new(5.0),
+            down:        false,
+            bandpass:    false,
+            pedal:       Smooth::new(0.0),
+            mix:         Smooth::new(1.0),
+            rate:        SAMPLE_RATE,
+            detector:    Detector::default(),
+            filter:      Filter::default(),
+        };
+        ef.detector.mode(Mode::Peak);
+        ef.prepare(SAMPLE_RATE, 0);
+        ef.reset();
+        ef
+    }
+}
+
+impl Effect for EnvelopeFilter {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sensitivity = 4.0;
+        self.attack(0.005);
+        self.release(0.1);
+        self.freq.reset(250.0);
+        self.range = 4.0;
+        self.q.reset(5.0);
+        self.down = false;
+        self.bandpass = false;
+        self.pedal.reset(0.0);
+        self.mix.reset(1.0);
+        self.detector.reset();
+        self.filter.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.detector.sample_rate(sample_rate);
+        self.freq.sample_rate(sample_rate);
+        self.q.sample_rate(sample_rate);
+        self.pedal.sample_rate(sample_rate);
+        self.mix.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.sensitivity(val); },
+            1 => { self.attack(val); },
+            2 => { self.release(val); },
+            3 => { self.freq(val); },
+            4 => { self.range(val); },
+            5 => { self.q(val); },
+            6 => { self.down(val >= 0.5); },
+            7 => { self.bandpass(val >= 0.5); },
+            8 => { self.pedal(val); },
+            9 => { self.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.sensitivity),
+            1 => Ok(self.attack),
+            2 => Ok(self.release),
+            3 => Ok(self.freq.target()),
+            4 => Ok(self.range),
+            5 => Ok(self.q.target()),
+            6 => Ok(if self.down { 1.0 } else { 0.0 }),
+            7 => Ok(if self.bandpass { 1.0 } else { 0.0 }),
+            8 => Ok(self.pedal.target()),
+            9 => Ok(self.mix.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl EnvelopeFilter {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let env = self.detector.next(smpl_in) * self.sensitivity;
+        let pos = env + self.pedal.next();
+        let pos = if pos > 1.0 { 1.0 } else { pos };
+        let pos = if self.down { 1.0 - pos } else { pos };
+
+        let freq = self.freq.next() * exp2f(self.range * pos);
+        self.filter.tune(freq, self.q.next(), self.rate);
+
+        let out = self.filter.tick(smpl_in);
+        let wet = if self.bandpass { out.bp } else { out.lp };
+        let mix = self.mix.next();
+        (smpl_in * (1.0 - mix)) + (wet * mix)
+    }
+
+///
+///Sweep position per unit of input level.
+///
+    pub fn sensitivity(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.sensitivity = val;
+        new
+    }
+
+    pub fn attack(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.attack = sec;
+        new.detector.attack(sec);
+        new
+    }
+
+    pub fn release(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.release = sec;
+        new.detector.release(sec);
+        new
+    }
+
+///
+///Bottom of the sweep in Hz.
+///
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq.set(hz);
+        new
+    }
+
+///
+///Sweep width in octaves.
+///
+    pub fn range(&mut self, oct: SampleType) -> &mut Self {
+        let new = self;
+        new.range = oct;
+        new
+    }
+
+    pub fn q(&mut self, q: SampleType) -> &mut Self {
+        let new = self;
+        new.q.set(q);
+        new
+    }
+
+///
+///Sweep down from the top of the range as level rises.
+///
+    pub fn down(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.down = on;
+        new
+    }
+
+///
+///Band pass output instead of low pass.
+///
+    pub fn bandpass(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.bandpass = on;
+        new
+    }
+
+///
+///Manual sweep position 0..1, added to the envelope.
+///
+    pub fn pedal(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.pedal.set(val);
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::envfilter::EnvelopeFilter;
+    use crate::{Effect, SAMPLE_RATE, SampleType};
+
+    fn peak(ef: &mut EnvelopeFilter, freq: SampleType) -> SampleType {
+        let mut max: SampleType = 0.0;
+        for i in 0..48000 {
+            let x = (2.0 * core::f32::consts::PI * freq * (i as SampleType) / SAMPLE_RATE).sin();
+            let y = ef.process(x * 0.1);
+            if i > 24000 {
+                max = max.max(y.abs());
+            }
+        }
+        max
+    }
+
+    #[test]
+    fn pedal() {
+//With no envelope the pedal alone opens the low pass.
+        let mut ef = EnvelopeFilter::default();
+        ef.sensitivity(0.0).freq(200.0).range(4.0).q(0.7);
+        let closed = peak(&mut ef, 2000.0);
+        ef.pedal(1.0);
+        let open = peak(&mut ef, 2000.0);
+        assert!(closed < 0.02);
+        assert!(open > 0.05);
+    }
+}
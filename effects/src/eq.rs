@@ -0,0 +1,253 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use crate::Effect;
+use crate::biquad::{Biquad, Mode};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::powf;
+use core::f32::consts::FRAC_1_SQRT_2;
+
+///
+///Number of EQ bands.
+///
+pub const NUM_BANDS: usize = 5;
+
+///
+///Parameters per band in order type, freq, gain, q.
+///
+const BAND_PARAMS: usize = 4;
+
+///
+///Band parameter order mapped onto Biquad parameter indexes.
+///
+const BIQUAD_PARAM: [usize; BAND_PARAMS] = [0, 1, 3, 2];
+
+///
+///Type is a biquad::Mode. Defaults are a low shelf, three peaks and a
+///high shelf all at 0dB.
+///
+static PARAMS: [Desc; (NUM_BANDS * BAND_PARAMS) + 1] = [
+    Desc { name: "b1type", min: 0.0,   max: 6.0,     default: 5.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "b1freq", min: 20.0,  max: 20000.0, default: 100.0, unit: Unit::Hz, curve: Curve::Log },
+    Desc { name: "b1gain", min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+    Desc { name: "b1q",    min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "b2type", min: 0.0,   max: 6.0,     default: 4.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "b2freq", min: 20.0,  max: 20000.0, default: 250.0, unit: Unit::Hz, curve: Curve::Log },
+    Desc { name: "b2gain", min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+    Desc { name: "b2q",    min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "b3type", min: 0.0,   max: 6.0,     default: 4.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "b3freq", min: 20.0,  max: 20000.0, default: 1000.0, unit: Unit::Hz, curve: Curve::Log },
+    Desc { name: "b3gain", min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+    Desc { name: "b3q",    min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "b4type", min: 0.0,   max: 6.0,     default: 4.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "b4freq", min: 20.0,  max: 20000.0, default: 4000.0, unit: Unit::Hz, curve: Curve::Log },
+    Desc { name: "b4gain", min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+    Desc { name: "b4q",    min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "b5type", min: 0.0,   max: 6.0,     default: 6.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "b5freq", min: 20.0,  max: 20000.0, default: 10000.0, unit: Unit::Hz, curve: Curve::Log },
+    Desc { name: "b5gain", min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+    Desc { name: "b5q",    min: 0.1,   max: 20.0,    default: FRAC_1_SQRT_2, unit: Unit::None, curve: Curve::Log },
+    Desc { name: "trim",    min: -24.0, max: 24.0,    default: 0.0, unit: Unit::Db, curve: Curve::Linear },
+];
+
+///
+///Convert decibels to a linear gain.
+///
+#[inline]
+fn db_to_gain(db: SampleType) -> SampleType {
+    powf(10.0, db / 20.0)
+}
+
+/***********************************************************************
+ * ParametricEq
+ **********************************************************************/
+
+///
+///Parametric equalizer. Bands are biquads in series followed by an 
+///output trim.
+///
+pub struct ParametricEq {
+    bands:   [Biquad; NUM_BANDS],
+    trim_db: SampleType, //Output trim in dB.
+    trim:    Smooth,     //Output trim as linear gain.
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        let mut eq = ParametricEq {
+            bands:   Default::default(),
+            trim_db: 0.0,
+            trim:    Smooth::new(1.0),
+        };
+        eq.defaults();
+        eq
+    }
+}
+
+impl Effect for ParametricEq {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.reset();
+        }
+        self.defaults();
+        self.trim_db = 0.0;
+        self.trim.reset(1.0);
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, max_block: usize) {
+        for band in self.bands.iter_mut() {
+            band.prepare(sample_rate, max_block);
+        }
+        self.trim.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        if idx == NUM_BANDS * BAND_PARAMS {
+            self.trim(val);
+            Ok(())
+        } else {
+            self.bands[idx / BAND_PARAMS].set_param(BIQUAD_PARAM[idx % BAND_PARAMS], val)
+        }
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        if idx == NUM_BANDS * BAND_PARAMS {
+            Ok(self.trim_db)
+        } else if idx < NUM_BANDS * BAND_PARAMS {
+            self.bands[idx / BAND_PARAMS].get_param(BIQUAD_PARAM[idx % BAND_PARAMS])
+        } else {
+            Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl ParametricEq {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let mut smpl = smpl_in;
+        for band in self.bands.iter_mut() {
+            smpl = band.process(smpl);
+        }
+        smpl * self.trim.next()
+    }
+
+///
+///Snap bands to their default type, frequency, gain and q.
+///
+    fn defaults(&mut self) {
+        for (i, band) in self.bands.iter_mut().enumerate() {
+            let p = &PARAMS[i * BAND_PARAMS..];
+            band.mode(Mode::from_param(p[0].default))
+                .freq(p[1].default)
+                .gain(p[2].default)
+                .q(p[3].default)
+                .settle();
+        }
+    }
+
+///
+///Band filter. Panics if band >= NUM_BANDS.
+///
+    pub fn band(&mut self, band: usize) -> &mut Biquad {
+        &mut self.bands[band]
+    }
+
+///
+///Output trim in dB.
+///
+    pub fn trim(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.trim_db = db;
+        new.trim.set(db_to_gain(db));
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eq::{ParametricEq, NUM_BANDS};
+    use crate::{Effect, SAMPLE_RATE};
+
+    #[test]
+    fn bands() {
+//Flat by default.
+        let mut eq = ParametricEq::default();
+        let mut out = 0.0;
+        for _ in 0..10000 {
+            out = eq.process(1.0);
+        }
+        assert!((out - 1.0).abs() < 0.001);
+
+//Band 2 gain is parameter 6. Trim is the last.
+        assert!(eq.set_param(6, 6.0).is_ok());
+        assert!(eq.get_param(6).ok() == Some(6.0));
+        assert!(eq.set_param(20, -6.0).is_ok());
+        assert!(eq.get_param(20).ok() == Some(-6.0));
+        assert!(eq.set_param(21, 0.0).is_err());
+        assert!(eq.set_param(7, 100.0).is_err());
+    }
+
+    #[test]
+    fn settles() {
+//Every band stops recalculating coefficients once its defaults settle.
+        let mut eq = ParametricEq::default();
+        for _ in 0..(SAMPLE_RATE as usize) {
+            eq.process(0.0);
+        }
+        for i in 0..NUM_BANDS {
+            assert!(eq.band(i).settled());
+        }
+    }
+
+    #[test]
+    fn reset() {
+//Bands land on their defaults after a reset instead of sweeping there.
+        let mut eq = ParametricEq::default();
+        eq.band(0).freq(5000.0).gain(-12.0);
+        for _ in 0..(SAMPLE_RATE as usize) {
+            eq.process(0.0);
+        }
+        eq.reset();
+        eq.process(0.0);
+        for i in 0..NUM_BANDS {
+            assert!(eq.band(i).settled());
+        }
+    }
+}
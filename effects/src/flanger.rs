@@ -0,0 +1,82 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use crate::moddelay::{Sweep, SweptDelay};
+use crate::param::{Desc, Unit, Curve};
+use crate::wave::SHAPE_MAX;
+
+///
+///Longest base delay and depth in seconds.
+///
+const DELAY_SECONDS: SampleType = 0.01;
+const DEPTH_SECONDS: SampleType = 0.005;
+
+static PARAMS: [Desc; 6] = [
+    Desc { name: "rate",     min: 0.01,   max: 10.0,          default: 0.25,   unit: Unit::Hz,      curve: Curve::Log },
+    Desc { name: "depth",    min: 0.0,    max: DEPTH_SECONDS, default: 0.0015, unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "shape",    min: 0.0,    max: SHAPE_MAX,     default: 1.0,    unit: Unit::None,    curve: Curve::Stepped },
+    Desc { name: "delay",    min: 0.0001, max: DELAY_SECONDS, default: 0.002,  unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "feedback", min: -0.95,  max: 0.95,          default: 0.5,    unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "mix",      min: 0.0,    max: 1.0,           default: 0.5,    unit: Unit::Ratio,   curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Flanger
+ **********************************************************************/
+
+///
+///Flanger. A single short swept delay. Negative feedback moves the 
+///comb notches up half a harmonic for a hollower sound.
+///
+pub type Flanger = SweptDelay<FlangerSweep>;
+
+pub struct FlangerSweep;
+
+impl Sweep for FlangerSweep {
+    fn params() -> &'static [Desc] { &PARAMS }
+    fn voices() -> usize { 1 }
+    fn max_seconds() -> SampleType { DELAY_SECONDS + DEPTH_SECONDS }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flanger::Flanger;
+    use crate::Effect;
+
+    #[test]
+    fn negative_feedback() {
+        let mut fl = Flanger::default();
+        assert!(fl.set_param(4, -0.9).is_ok());
+        assert!(fl.get_param(4).ok() == Some(-0.9));
+        assert!(fl.set_param(4, -1.0).is_err());
+
+//External modulation input stays stable at the extremes.
+        let audio = [1.0; 4096];
+        let ext   = [-4.0; 4096];
+        let mut out = [0.0; 4096];
+        fl.process_ports(&[&audio, &ext], &mut [&mut out]);
+        assert!(out.iter().all(|s| s.is_finite() && s.abs() < 20.0));
+    }
+}
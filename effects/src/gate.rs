@@ -0,0 +1,329 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::detector::{Detector, Mode};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use libm::{expf, log10f, powf};
+
+///
+///Detector release. Short enough to track the signal, long enough not
+///to close on every zero crossing.
+///
+const DETECT_SECONDS: SampleType = 0.01;
+
+static PARAMS: [Desc; 7] = [
+    Desc { name: "threshold",  min: -80.0,  max: 0.0,  default: -50.0, unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "hysteresis", min: 0.0,    max: 20.0, default: 6.0,   unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "attack",     min: 0.0001, max: 0.1,  default: 0.001, unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "hold",       min: 0.0,    max: 2.0,  default: 0.05,  unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "release",    min: 0.005,  max: 4.0,  default: 0.1,   unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "range",      min: -80.0,  max: 0.0,  default: -80.0, unit: Unit::Db,      curve: Curve::Linear },
+    Desc { name: "key",        min: 0.0,    max: 1.0,  default: 0.0,   unit: Unit::None,    curve: Curve::Stepped },
+];
+
+/***********************************************************************
+ * Gate
+ **********************************************************************/
+
+///
+///Noise gate. Opens when the key rises above threshold and closes once
+///it falls below threshold - hysteresis for longer than hold. Closed,
+///the signal is attenuated by range so a small range acts as a gentle
+///downward expander.
+///
+///Input port 0 is audio. Input port 1 is the key, used instead of the
+///audio for detection when the key parameter is 1.
+///
+pub struct Gate {
+    threshold:  SampleType, //Open level in dB.
+    hysteresis: SampleType, //Close this many dB below threshold.
+    attack:     SampleType, //Seconds to open.
+    hold:       SampleType, //Seconds to stay open once below.
+    release:    SampleType, //Seconds to close.
+    range:      SampleType, //Attenuation when closed in dB.
+    key:        bool,       //Detect from input port 1.
+    rate:       SampleType, //Sample rate.
+
+    detector:   Detector,
+    open:       bool,
+    held:       usize,      //Samples left to hold open.
+    att:        SampleType, //Attack coefficient.
+    rel:        SampleType, //Release coefficient.
+    floor:      SampleType, //Range as linear gain.
+    gain:       SampleType, //Current gain.
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        let mut gate = Gate {
+            threshold:  -50.0,
+            hysteresis: 6.0,
+            attack:     0.001,
+            hold:       0.05,
+            release:    0.1,
+            range:      -80.0,
+            key:        false,
+            rate:       SAMPLE_RATE,
+            detector:   Detector::default(),
+            open:       false,
+            held:       0,
+            att:        0.0,
+            rel:        0.0,
+            floor:      0.0,
+            gain:       0.0,
+        };
+        gate.detector.mode(Mode::Peak).attack(0.0);
+        gate.prepare(SAMPLE_RATE, 0);
+        gate.range(-80.0);
+        gate.gain = gate.floor;
+        gate
+    }
+}
+
+#[inline]
+fn coef(sec: SampleType, rate: SampleType) -> SampleType {
+    expf(-1.0 / (sec * rate))
+}
+
+impl Effect for Gate {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in, smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in, *smpl_in);
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+
+///
+///Audio on port 0, key on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let output = match outputs.first_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        match inputs {
+            [audio, key, ..] if self.key => {
+                for ((smpl_out, smpl_in), smpl_key) in output.iter_mut()
+                                                             .zip(audio.iter())
+                                                             .zip(key.iter())
+                {
+                    *smpl_out = self.tick(*smpl_in, *smpl_key);
+                }
+            },
+            [audio, ..] => self.process_block(audio, output),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.threshold  = -50.0;
+        self.hysteresis = 6.0;
+        self.attack(0.001);
+        self.hold       = 0.05;
+        self.release(0.1);
+        self.range(-80.0);
+        self.key        = false;
+        self.detector.reset();
+        self.open = false;
+        self.held = 0;
+        self.gain = self.floor;
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.detector.sample_rate(sample_rate).release(DETECT_SECONDS);
+        self.att = coef(self.attack, sample_rate);
+        self.rel = coef(self.release, sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.threshold(val); },
+            1 => { self.hysteresis(val); },
+            2 => { self.attack(val); },
+            3 => { self.hold(val); },
+            4 => { self.release(val); },
+            5 => { self.range(val); },
+            6 => { self.key(val >= 0.5); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.threshold),
+            1 => Ok(self.hysteresis),
+            2 => Ok(self.attack),
+            3 => Ok(self.hold),
+            4 => Ok(self.release),
+            5 => Ok(self.range),
+            6 => Ok(if self.key { 1.0 } else { 0.0 }),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Gate {
+///
+///Gate smpl_in using the level of smpl_key.
+///
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType, smpl_key: SampleType) -> SampleType {
+        let lvl = self.detector.next(smpl_key);
+        let db  = if lvl > 0.0 { 20.0 * log10f(lvl) } else { -200.0 };
+
+        if db >= self.threshold {
+            self.open = true;
+            self.held = (self.hold * self.rate) as usize;
+        } else if self.open && db < (self.threshold - self.hysteresis) {
+            if self.held > 0 {
+                self.held -= 1;
+            } else {
+                self.open = false;
+            }
+        }
+
+        let (target, c) = if self.open { 
+            (1.0, self.att) 
+        } else { 
+            (self.floor, self.rel) 
+        };
+
+        self.gain = target + (c * (self.gain - target));
+        smpl_in * self.gain
+    }
+
+///
+///True while the gate is open (including hold).
+///
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn threshold(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.threshold = db;
+        new
+    }
+
+    pub fn hysteresis(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.hysteresis = if db < 0.0 { 0.0 } else { db };
+        new
+    }
+
+    pub fn attack(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.attack = sec;
+        new.att = coef(sec, new.rate);
+        new
+    }
+
+    pub fn hold(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.hold = if sec < 0.0 { 0.0 } else { sec };
+        new
+    }
+
+    pub fn release(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.release = sec;
+        new.rel = coef(sec, new.rate);
+        new
+    }
+
+///
+///Attenuation when closed in dB. -80 is effectively silence, 0 leaves
+///the signal untouched.
+///
+    pub fn range(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.range = db;
+        new.floor = powf(10.0, db / 20.0);
+        new
+    }
+
+///
+///Detect from the key input (port 1) instead of the audio.
+///
+    pub fn key(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.key = on;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gate::Gate;
+    use crate::Effect;
+
+    #[test]
+    fn hysteresis() {
+        let mut gate = Gate::default();
+        gate.threshold(-20.0).hysteresis(10.0).hold(0.0);
+
+//Below threshold stays closed.
+        for _ in 0..1000 { gate.process(0.05); }
+        assert!(!gate.is_open());
+
+//Opens above threshold, stays open between the two levels.
+        for _ in 0..1000 { gate.process(0.2); }
+        assert!(gate.is_open());
+        for _ in 0..10000 { gate.process(0.05); }
+        assert!(gate.is_open());
+
+//Closes below threshold - hysteresis.
+        for _ in 0..10000 { gate.process(0.01); }
+        assert!(!gate.is_open());
+    }
+
+    #[test]
+    fn key() {
+        let mut gate = Gate::default();
+        gate.key(true);
+        let audio = [0.001; 1000];
+        let key   = [0.5; 1000];
+        let mut out = [0.0; 1000];
+        gate.process_ports(&[&audio, &key], &mut [&mut out]);
+        assert!(gate.is_open());
+        assert!((out[999] - 0.001).abs() < 0.00001);
+    }
+}
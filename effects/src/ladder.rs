@@ -0,0 +1,247 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::{powf, tanf, tanhf};
+use core::f32::consts::PI;
+
+static PARAMS: [Desc; 3] = [
+    Desc { name: "freq",  min: 20.0, max: 20000.0, default: 1000.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "res",   min: 0.0,  max: 1.2,     default: 0.0,    unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "drive", min: 0.0,  max: 24.0,    default: 0.0,    unit: Unit::Db,    curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Ladder
+ **********************************************************************/
+
+///
+///Nonlinear 4 pole (24dB/octave) ladder low pass. Four trapezoidal one
+///pole stages with the feedback loop solved without a unit delay, then
+///saturated with tanh at the input of the first stage. 
+///
+///Cutoff and resonance are not smoothed so rack connections can sweep
+///them at audio rate. Resonance 1 is the edge of oscillation and above
+///it the filter sings at the cutoff frequency once excited.
+///
+pub struct Ladder {
+    freq:  SampleType,      //Cutoff in Hz.
+    res:   SampleType,      //Resonance 0..1.2.
+    drive: Smooth,          //Input gain in dB.
+    gain:  SampleType,      //Linear input gain, updated while drive moves.
+    rate:  SampleType,      //Sample rate.
+    dirty: bool,            //Coefficient needs recomputing.
+    g:     SampleType,      //One pole gain g / (1 + g).
+    s:     [SampleType; 4], //Stage states.
+}
+
+impl Default for Ladder {
+    fn default() -> Self {
+        let mut ladder = Ladder {
+            freq:  1000.0,
+            res:   0.0,
+            drive: Smooth::new(0.0),
+            gain:  1.0,
+            rate:  SAMPLE_RATE,
+            dirty: true,
+            g:     0.0,
+            s:     [0.0; 4],
+        };
+        ladder.prepare(SAMPLE_RATE, 0);
+        ladder
+    }
+}
+
+impl Effect for Ladder {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq(1000.0);
+        self.res(0.0);
+        self.drive.reset(0.0);
+        self.gain = 1.0;
+        self.s = [0.0; 4];
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate  = sample_rate;
+        self.dirty = true;
+        self.drive.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.freq(val); },
+            1 => { self.res(val); },
+            2 => { self.drive(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.freq),
+            1 => Ok(self.res),
+            2 => Ok(self.drive.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Ladder {
+///
+///Each stage outputs g * x + s / (1 + g). Summing the state terms down
+///the cascade gives the 4th stage output as g^4 * u + sum, so the input
+///u = x - k * y4 can be solved before any stage runs.
+///
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        if self.dirty {
+            let max  = self.rate * 0.49;
+            let freq = if self.freq > max { max } else { self.freq };
+            let g    = tanf(PI * freq / self.rate);
+            self.g     = g / (1.0 + g);
+            self.dirty = false;
+        }
+
+        let g  = self.g;
+        let k  = 4.0 * self.res;
+        let g2 = g * g;
+        let b  = 1.0 - g;
+
+        let sum = (g2 * g * self.s[0] * b) + (g2 * self.s[1] * b) + 
+                  (g * self.s[2] * b) + (self.s[3] * b);
+        if !self.drive.settled() {
+            self.gain = powf(10.0, self.drive.next() / 20.0);
+        }
+        let u = tanhf(((smpl_in * self.gain) - (k * sum)) / (1.0 + (k * g2 * g2)));
+
+        let mut x = u;
+        for s in self.s.iter_mut() {
+            let v = (x - *s) * g;
+            x  = v + *s;
+            *s = x + v;
+        }
+
+        if !x.is_finite() {
+            self.s = [0.0; 4];
+            return 0.0;
+        }
+
+        x
+    }
+
+///
+///Cutoff in Hz.
+///
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq  = hz;
+        new.dirty = true;
+        new
+    }
+
+///
+///Resonance. 1 is the edge of self oscillation.
+///
+    pub fn res(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.res = val;
+        new
+    }
+
+///
+///Input drive into the saturator in dB.
+///
+    pub fn drive(&mut self, db: SampleType) -> &mut Self {
+        let new = self;
+        new.drive.set(db);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ladder::Ladder;
+    use crate::{Effect, SampleType};
+
+    #[test]
+    fn ladder() {
+//Small DC passes unchanged without resonance.
+        let mut ladder = Ladder::default();
+        let mut out = 0.0;
+        for _ in 0..4800 {
+            out = ladder.process(0.1);
+        }
+        assert!((out - 0.1).abs() < 0.001);
+
+//Above 1 resonance it keeps singing after a kick.
+        let mut ladder = Ladder::default();
+        ladder.freq(440.0).res(1.1);
+        ladder.process(0.5);
+        let mut max: SampleType = 0.0;
+        for i in 0..96000 {
+            let out = ladder.process(0.0);
+            if i > 48000 {
+                max = max.max(out.abs());
+            }
+        }
+        assert!(max > 0.1 && max < 2.0);
+    }
+
+    #[test]
+    fn drive() {
+//Small DC comes out scaled by the settled drive and unscaled after a
+//reset.
+        let mut ladder = Ladder::default();
+        ladder.drive(12.0);
+        let mut out = 0.0;
+        for _ in 0..4800 {
+            out = ladder.process(0.01);
+        }
+        assert!((out - 0.0398).abs() < 0.001);
+
+        ladder.reset();
+        for _ in 0..4800 {
+            out = ladder.process(0.01);
+        }
+        assert!((out - 0.01).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,398 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, wrap};
+use common::rng::Rng;
+use libm::cosf;
+use core::f32::consts::PI;
+
+///
+///Tempo synced note lengths in beats (quarter notes), longest first. 
+///Division parameter values index this table.
+///
+static DIVISIONS: [SampleType; 10] = [
+    4.0,       //1/1
+    2.0,       //1/2
+    1.5,       //1/4 dotted
+    1.0,       //1/4
+    2.0 / 3.0, //1/4 triplet
+    0.75,      //1/8 dotted
+    0.5,       //1/8
+    1.0 / 3.0, //1/8 triplet
+    0.25,      //1/16
+    0.125,     //1/32
+];
+
+static PARAMS: [Desc; 10] = [
+    Desc { name: "rate",     min: 0.01,     max: 50.0,      default: 1.0,   unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "sync",     min: 0.0,      max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "tempo",    min: 20.0,     max: 300.0,     default: 120.0, unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "division", min: 0.0,      max: 9.0,       default: 3.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "shape",    min: 0.0,      max: 6.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "phase",    min: 0.0,      max: 1.0,       default: 0.0,   unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "depth",    min: 0.0,      max: 20000.0,   default: 1.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "offset",   min: -20000.0, max: 20000.0,   default: 0.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "polarity", min: 0.0,      max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "retrig",   min: 0.0,      max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+];
+
+/***********************************************************************
+ * LfoShape
+ **********************************************************************/
+
+///
+///Lfo waveforms. The periodic shapes are shared with the modulation 
+///effects. The random shapes pick a new value -1..1 every cycle and 
+///either hold it or glide to it.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum LfoShape {
+    Wave(Shape),
+    SampleHold,
+    Random,
+}
+
+impl LfoShape {
+    pub fn from_param(val: SampleType) -> LfoShape {
+        match (val + 0.5) as usize {
+            0 => LfoShape::Wave(Shape::Sine),
+            1 => LfoShape::Wave(Shape::Triangle),
+            2 => LfoShape::Wave(Shape::Square),
+            3 => LfoShape::Wave(Shape::SawUp),
+            4 => LfoShape::Wave(Shape::SawDown),
+            5 => LfoShape::SampleHold,
+            _ => LfoShape::Random,
+        }
+    }
+
+    pub fn to_param(&self) -> SampleType {
+        match self {
+            LfoShape::Wave(shape) => shape.to_param(),
+            LfoShape::SampleHold  => 5.0,
+            LfoShape::Random      => 6.0,
+        }
+    }
+}
+
+/***********************************************************************
+ * Lfo
+ **********************************************************************/
+
+///
+///Low frequency oscillator meant for rack parameter connections. Rate
+///is free in Hz or follows tempo as a note division. Output is the 
+///shape (-1..1, or 0..1 when unipolar) times depth plus offset. 
+///
+///The process input is the retrigger. When retrig is on the cycle 
+///restarts at phase whenever the input rises above 0.5.
+///
+pub struct Lfo {
+    freq:     SampleType, //Free rate in Hz.
+    synced:   bool,       //Rate from tempo and division.
+    tempo:    SampleType, //Beats per minute.
+    division: usize,      //Index into DIVISIONS.
+    shape:    LfoShape,
+    phase:    SampleType, //Phase offset in cycles.
+    depth:    Smooth,     //Output is scaled by this amount.
+    offset:   Smooth,     //Then offset by this amount.
+    unipolar: bool,       //0..1 instead of -1..1.
+    retrig:   bool,       //Restart on rising process input.
+
+    lfo:      Phasor,
+    last:     SampleType, //Previous phase, to spot the cycle restarting.
+    trig:     SampleType, //Previous retrigger input.
+    prev:     SampleType, //Random value at the start of the cycle.
+    target:   SampleType, //Random value at the end of the cycle.
+    rng:      Rng,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        let mut lfo = Lfo {
+            freq:     1.0,
+            synced:   false,
+            tempo:    120.0,
+            division: 3,
+            shape:    LfoShape::Wave(Shape::Sine),
+            phase:    0.0,
+            depth:    Smooth::new(1.0),
+            offset:   Smooth::new(0.0),
+            unipolar: false,
+            retrig:   false,
+            lfo:      Phasor::default(),
+            last:     0.0,
+            trig:     0.0,
+            prev:     0.0,
+            target:   0.0,
+            rng:      Rng::default(),
+        };
+        lfo.prepare(SAMPLE_RATE, 0);
+        lfo.restart();
+        lfo
+    }
+}
+
+impl Effect for Lfo {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq     = 1.0;
+        self.synced   = false;
+        self.tempo    = 120.0;
+        self.division = 3;
+        self.shape    = LfoShape::Wave(Shape::Sine);
+        self.phase    = 0.0;
+        self.depth.reset(1.0);
+        self.offset.reset(0.0);
+        self.unipolar = false;
+        self.retrig   = false;
+        self.trig     = 0.0;
+        self.restart();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.lfo.sample_rate(sample_rate);
+        self.depth.sample_rate(sample_rate);
+        self.offset.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.freq(val); },
+            1 => { self.synced(val >= 0.5); },
+            2 => { self.tempo(val); },
+            3 => { self.division((val + 0.5) as usize); },
+            4 => { self.shape(LfoShape::from_param(val)); },
+            5 => { self.phase(val); },
+            6 => { self.depth(val); },
+            7 => { self.offset(val); },
+            8 => { self.unipolar(val >= 0.5); },
+            9 => { self.retrig(val >= 0.5); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.freq),
+            1 => Ok(if self.synced { 1.0 } else { 0.0 }),
+            2 => Ok(self.tempo),
+            3 => Ok(self.division as SampleType),
+            4 => Ok(self.shape.to_param()),
+            5 => Ok(self.phase),
+            6 => Ok(self.depth.target()),
+            7 => Ok(self.offset.target()),
+            8 => Ok(if self.unipolar { 1.0 } else { 0.0 }),
+            9 => Ok(if self.retrig { 1.0 } else { 0.0 }),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Lfo {
+    #[inline]
+    fn tick(&mut self, trig: SampleType) -> SampleType {
+        if self.retrig && self.trig <= 0.5 && trig > 0.5 {
+            self.restart();
+        }
+        self.trig = trig;
+
+        let freq  = self.rate();
+        let phase = wrap(self.lfo.next(freq) + self.phase);
+
+//New random values when the offset phase starts a new cycle.
+        if phase < self.last {
+            self.prev   = self.target;
+            self.target = self.rng.next_bipolar();
+        }
+        self.last = phase;
+
+        let val = match self.shape {
+            LfoShape::Wave(shape) => shape.value(phase),
+            LfoShape::SampleHold  => self.target,
+            LfoShape::Random      => {
+                let x = 0.5 - (0.5 * cosf(PI * phase));
+                self.prev + ((self.target - self.prev) * x)
+            },
+        };
+
+        let val = if self.unipolar { 0.5 + (0.5 * val) } else { val };
+        (val * self.depth.next()) + self.offset.next()
+    }
+
+///
+///Start the cycle over at the phase offset.
+///
+    fn restart(&mut self) {
+        self.lfo.reset(0.0);
+        self.last   = 1.0;
+        self.target = self.rng.next_bipolar();
+    }
+
+///
+///Current rate in Hz, free or from tempo.
+///
+    pub fn rate(&self) -> SampleType {
+        if self.synced {
+            (self.tempo / 60.0) / DIVISIONS[self.division]
+        } else {
+            self.freq
+        }
+    }
+
+///
+///Free running rate in Hz.
+///
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq = hz;
+        new
+    }
+
+///
+///Follow tempo and division instead of freq.
+///
+    pub fn synced(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.synced = on;
+        new
+    }
+
+    pub fn tempo(&mut self, bpm: SampleType) -> &mut Self {
+        let new = self;
+        new.tempo = bpm;
+        new
+    }
+
+///
+///Note division, 0 (whole note) .. 9 (1/32). See DIVISIONS.
+///
+    pub fn division(&mut self, idx: usize) -> &mut Self {
+        let new = self;
+        new.division = if idx >= DIVISIONS.len() { DIVISIONS.len() - 1 } else { idx };
+        new
+    }
+
+    pub fn shape(&mut self, shape: LfoShape) -> &mut Self {
+        let new = self;
+        new.shape = shape;
+        new
+    }
+
+///
+///Phase offset in cycles.
+///
+    pub fn phase(&mut self, cycles: SampleType) -> &mut Self {
+        let new = self;
+        new.phase = wrap(cycles);
+        new
+    }
+
+    pub fn depth(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.depth.set(val);
+        new
+    }
+
+    pub fn offset(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.offset.set(val);
+        new
+    }
+
+    pub fn unipolar(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.unipolar = on;
+        new
+    }
+
+    pub fn retrig(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.retrig = on;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lfo::{Lfo, LfoShape};
+    use crate::wave::Shape;
+    use crate::Effect;
+
+    #[test]
+    fn tempo() {
+//120 BPM quarter notes is 2Hz. Count the rising saw resets in 2.25s.
+        let mut lfo = Lfo::default();
+        lfo.synced(true).tempo(120.0).division(3).shape(LfoShape::Wave(Shape::SawUp));
+        let mut last = lfo.process(0.0);
+        let mut cnt = 0;
+        for _ in 1..108000 {
+            let val = lfo.process(0.0);
+            if val < last {
+                cnt += 1;
+            }
+            last = val;
+        }
+        assert!(cnt == 4);
+    }
+
+    #[test]
+    fn shapes() {
+        for shape in 0..7 {
+            let mut lfo = Lfo::default();
+            lfo.freq(10.0).unipolar(true).shape(LfoShape::from_param(shape as f32));
+            for _ in 0..48000 {
+                let val = lfo.process(0.0);
+                assert!((0.0..=1.0).contains(&val));
+            }
+        }
+    }
+
+    #[test]
+    fn retrig() {
+        let mut lfo = Lfo::default();
+        lfo.retrig(true).phase(0.25);
+        for _ in 0..1000 {
+            lfo.process(0.0);
+        }
+        assert!((lfo.process(1.0) - 1.0).abs() < 1e-4);
+    }
+}
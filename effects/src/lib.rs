@@ -21,26 +21,123 @@ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
 
 pub type SampleType = f32;
+
+///
+///Default sample rate. Effects start out at this rate until prepare()
+///tells them otherwise.
+///
 pub const SAMPLE_RATE: SampleType = 48000.0;
 pub const SAMPLE_RATE_USIZE: usize = SAMPLE_RATE as usize;
 
+///
+///Maximum number of input or output ports an effect can declare.
+///
+pub const MAX_PORTS: usize = 4;
+
 pub mod thru;
 pub mod delay;
 pub mod pwm;
 pub mod sine;
 pub mod constant;
+pub mod param;
+pub mod smooth;
+pub mod delayline;
+pub mod biquad;
+pub mod eq;
+pub mod detector;
+pub mod compressor;
+pub mod gate;
+pub mod wave;
+pub mod moddelay;
+pub mod chorus;
+pub mod flanger;
+pub mod phaser;
+pub mod reverb;
+pub mod plate;
+pub mod convolve;
+pub mod oversample;
+pub mod distortion;
+pub mod crusher;
+pub mod ringmod;
+pub mod tremolo;
+pub mod pitch;
+pub mod svf;
+pub mod envfilter;
+pub mod ladder;
+pub mod osc;
+pub mod lfo;
+pub mod envelope;
 
 ///
 ///Common trait implemented by all effects.
 ///
 pub trait Effect {
     fn process(&mut self, smpl_in: SampleType) -> SampleType { smpl_in }
+
+///
+///Process a block of samples. Input and output are expected to be the
+///same length. Default falls back to calling process() per sample.
+///
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.process(*smpl_in);
+        }
+    }
+
+///
+///Number of input and output ports. Mono effects have one of each.
+///Multichannel effects (stereo, side-chain, etc.) declare more, up to
+///MAX_PORTS.
+///
+    fn num_inputs(&mut self) -> usize { 1 }
+    fn num_outputs(&mut self) -> usize { 1 }
+
+///
+///Process a block of samples on every port. There is one input slice
+///per input port and one output slice per output port, all the same 
+///length. Default processes port 0 with process_block().
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        if let (Some(input), Some(output)) = (inputs.first(), outputs.first_mut()) {
+            self.process_block(input, output);
+        }
+    }
+
     fn reset(&mut self) {}
-    fn num_params(&mut self) -> usize { 0 }
-    fn set_param(&mut self, _idx: usize, _val: SampleType) {}
-    fn get_param(&mut self, _idx: usize) -> SampleType { SampleType::default() }  
+
+///
+///Called before processing starts or when the sample rate changes.
+///max_block is the longest block process_block() or process_ports()
+///will be given. Effects derive their internal constants from the 
+///sample rate and may allocate here. Not called from the audio path.
+///
+    fn prepare(&mut self, _sample_rate: SampleType, _max_block: usize) {}
+
+///
+///Descriptors for each parameter, indexed the same as set_param() and
+///get_param().
+///
+    fn params(&mut self) -> &'static [param::Desc] { &[] }
+    fn num_params(&mut self) -> usize { self.params().len() }
+
+///
+///Set and get parameters. Setting fails if the parameter doesn't exist 
+///or the value is not finite or out of the descriptor's range.
+///
+    fn set_param(&mut self, _idx: usize, _val: SampleType) -> Result<(), param::ERROR> { 
+        Err(param::ERROR::INDEX)
+    }
+
+    fn get_param(&mut self, _idx: usize) -> Result<SampleType, param::ERROR> { 
+        Err(param::ERROR::INDEX)
+    }
 }
 
@@ -0,0 +1,327 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::delayline::{DelayLine, Interp};
+use crate::param;
+use crate::param::{Desc, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, wrap};
+use core::marker::PhantomData;
+
+///
+///Most voices a ModDelay can read.
+///
+pub const MAX_VOICES: usize = 4;
+
+/***********************************************************************
+ * ModDelay
+ **********************************************************************/
+
+///
+///Delay line read by one or more LFO swept taps. Core of the chorus 
+///and flanger. Voices are spread evenly around the LFO cycle.
+///
+///Modulation is the LFO plus an external value, limited to -1..1, 
+///scaled by depth around the base delay.
+///
+pub struct ModDelay {
+    freq:     SampleType, //LFO rate in Hz.
+    shape:    Shape,      //LFO waveform.
+    depth:    Smooth,     //Sweep either side of delay in seconds.
+    delay:    Smooth,     //Base delay in seconds.
+    feedback: Smooth,     //Wet signal fed back to the line.
+    mix:      Smooth,     //0 dry .. 1 wet.
+    voices:   usize,      //Number of taps.
+    max:      SampleType, //Longest delay + depth in seconds.
+    rate:     SampleType, //Sample rate.
+    lfo:      Phasor,
+    line:     DelayLine,
+}
+
+impl ModDelay {
+///
+///New core able to delay up to max seconds including depth.
+///
+    pub fn new(max: SampleType, voices: usize) -> Self {
+        let mut line = DelayLine::new(line_len(max, SAMPLE_RATE));
+        line.interp(Interp::Hermite);
+
+        ModDelay {
+            freq:     1.0,
+            shape:    Shape::Sine,
+            depth:    Smooth::new(0.0),
+            delay:    Smooth::new(0.0),
+            feedback: Smooth::new(0.0),
+            mix:      Smooth::new(0.5),
+            voices:   voices.clamp(1, MAX_VOICES),
+            max,
+            rate:     SAMPLE_RATE,
+            lfo:      Phasor::default(),
+            line,
+        }
+    }
+
+///
+///Process one sample with ext added to the LFO.
+///
+    #[inline]
+    pub fn tick(&mut self, smpl_in: SampleType, ext: SampleType) -> SampleType {
+        let phase = self.lfo.next(self.freq);
+        let delay = self.delay.next();
+        let depth = self.depth.next();
+        let step  = 1.0 / (self.voices as SampleType);
+
+        let mut wet = 0.0;
+        for v in 0..self.voices {
+            let lfo = self.shape.value(wrap(phase + (step * (v as SampleType)))) + ext;
+            let lfo = lfo.clamp(-1.0, 1.0);
+            wet += self.line.read((delay + (depth * lfo)) * self.rate);
+        }
+        wet *= step;
+
+        self.line.write(smpl_in + (wet * self.feedback.next()));
+
+        let mix = self.mix.next();
+        (smpl_in * (1.0 - mix)) + (wet * mix)
+    }
+
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.lfo.reset(0.0);
+    }
+
+///
+///Resize the line for the sample rate. Contents are cleared.
+///
+    pub fn sample_rate(&mut self, rate: SampleType) {
+        self.rate = rate;
+        self.line.resize(line_len(self.max, rate));
+        self.lfo.sample_rate(rate);
+        self.depth.sample_rate(rate);
+        self.delay.sample_rate(rate);
+        self.feedback.sample_rate(rate);
+        self.mix.sample_rate(rate);
+    }
+
+    pub fn get_freq(&self) -> SampleType { self.freq }
+    pub fn get_shape(&self) -> Shape { self.shape }
+    pub fn get_depth(&self) -> SampleType { self.depth.target() }
+    pub fn get_delay(&self) -> SampleType { self.delay.target() }
+    pub fn get_feedback(&self) -> SampleType { self.feedback.target() }
+    pub fn get_mix(&self) -> SampleType { self.mix.target() }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq = hz;
+        new
+    }
+
+    pub fn shape(&mut self, shape: Shape) -> &mut Self {
+        let new = self;
+        new.shape = shape;
+        new
+    }
+
+    pub fn depth(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.depth.set(sec);
+        new
+    }
+
+    pub fn delay(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.delay.set(sec);
+        new
+    }
+
+    pub fn feedback(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.feedback.set(val);
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+
+///
+///Jump every smoothed value to its target.
+///
+    pub fn settle(&mut self) {
+        let (depth, delay) = (self.depth.target(), self.delay.target());
+        let (feedback, mix) = (self.feedback.target(), self.mix.target());
+        self.depth.reset(depth);
+        self.delay.reset(delay);
+        self.feedback.reset(feedback);
+        self.mix.reset(mix);
+    }
+}
+
+/***********************************************************************
+ * Sweep
+ **********************************************************************/
+
+///
+///What sets one swept delay effect apart from another. Parameters are
+///rate, depth, shape, delay, feedback and mix in that order.
+///
+pub trait Sweep {
+    fn params() -> &'static [Desc];
+    fn voices() -> usize;
+
+///
+///Longest delay plus depth in seconds.
+///
+    fn max_seconds() -> SampleType;
+}
+
+/***********************************************************************
+ * SweptDelay
+ **********************************************************************/
+
+///
+///Effect around a ModDelay shared by the chorus and flanger. Input 
+///port 1 is added to the internal LFO, -1..1 sweeps the full depth.
+///
+pub struct SweptDelay<S: Sweep> {
+    core:  ModDelay,
+    sweep: PhantomData<S>,
+}
+
+impl<S: Sweep> Default for SweptDelay<S> {
+    fn default() -> Self {
+        let mut fx = SweptDelay {
+            core:  ModDelay::new(S::max_seconds(), S::voices()),
+            sweep: PhantomData,
+        };
+        fx.reset();
+        fx
+    }
+}
+
+impl<S: Sweep> Effect for SweptDelay<S> {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.core.tick(smpl_in, 0.0)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.core.tick(*smpl_in, 0.0);
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+
+///
+///Audio on port 0, external modulation on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let output = match outputs.first_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        match inputs {
+            [audio, ext, ..] => {
+                for ((smpl_out, smpl_in), smpl_ext) in output.iter_mut()
+                                                             .zip(audio.iter())
+                                                             .zip(ext.iter())
+                {
+                    *smpl_out = self.core.tick(*smpl_in, *smpl_ext);
+                }
+            },
+            [audio] => self.process_block(audio, output),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        let params = S::params();
+        self.core.freq(params[0].default)
+                 .depth(params[1].default)
+                 .shape(Shape::from_param(params[2].default))
+                 .delay(params[3].default)
+                 .feedback(params[4].default)
+                 .mix(params[5].default);
+        self.core.settle();
+        self.core.clear();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.core.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { S::params() }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(S::params(), idx, val)?;
+        match idx {
+            0 => { self.core.freq(val); },
+            1 => { self.core.depth(val); },
+            2 => { self.core.shape(Shape::from_param(val)); },
+            3 => { self.core.delay(val); },
+            4 => { self.core.feedback(val); },
+            5 => { self.core.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.core.get_freq()),
+            1 => Ok(self.core.get_depth()),
+            2 => Ok(self.core.get_shape().to_param()),
+            3 => Ok(self.core.get_delay()),
+            4 => Ok(self.core.get_feedback()),
+            5 => Ok(self.core.get_mix()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl<S: Sweep> SweptDelay<S> {
+///
+///Underlying modulated delay for setting values directly.
+///
+    pub fn core(&mut self) -> &mut ModDelay {
+        &mut self.core
+    }
+}
+
+///
+///Line length in samples for max seconds. Room is left for Hermite
+///interpolation.
+///
+#[inline]
+fn line_len(max: SampleType, rate: SampleType) -> usize {
+    ((max * rate) as usize) + 4
+}
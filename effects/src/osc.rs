@@ -0,0 +1,465 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::sine::taylor;
+use core::f32::consts::TAU;
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, wrap};
+use libm::fabsf;
+
+///
+///Sine table length. One extra entry is kept so interpolation never 
+///wraps.
+///
+const TABLE_LEN: usize = 256;
+
+static PARAMS: [Desc; 5] = [
+    Desc { name: "wave",   min: 0.0,      max: WAVE_MAX, default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "freq",   min: 0.01,     max: 20000.0,  default: 440.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "width",  min: 0.0,      max: 1.0,      default: 0.5,   unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "scale",  min: -20000.0, max: 20000.0,  default: 1.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "offset", min: -20000.0, max: 20000.0,  default: 0.0,   unit: Unit::None,  curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Wave
+ **********************************************************************/
+
+///
+///Oscillator waveforms.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Wave {
+    Sine,     //Table lookup.
+    Triangle, //PolyBLAMP corrected corners.
+    Saw,      //PolyBLEP corrected ramp up.
+    Square,   //PolyBLEP corrected, 50% duty.
+    Pulse,    //PolyBLEP corrected, variable width.
+}
+
+///
+///Highest wave parameter value.
+///
+pub const WAVE_MAX: SampleType = 4.0;
+
+impl Wave {
+///
+///Wave from parameter value. Values are rounded and out of range 
+///values pick the nearest wave.
+///
+    pub fn from_param(val: SampleType) -> Wave {
+        match (val + 0.5) as usize {
+            0 => Wave::Sine,
+            1 => Wave::Triangle,
+            2 => Wave::Saw,
+            3 => Wave::Square,
+            _ => Wave::Pulse,
+        }
+    }
+
+    pub fn to_param(&self) -> SampleType {
+        match self {
+            Wave::Sine     => 0.0,
+            Wave::Triangle => 1.0,
+            Wave::Saw      => 2.0,
+            Wave::Square   => 3.0,
+            Wave::Pulse    => 4.0,
+        }
+    }
+}
+
+///
+///PolyBLEP residual for a unit step down at phase 0. t is the phase
+///and dt the phase increment per sample.
+///
+#[inline]
+fn blep(t: SampleType, dt: SampleType) -> SampleType {
+    if t < dt {
+        let x = t / dt;
+        (2.0 * x) - (x * x) - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        (x * x) + (2.0 * x) + 1.0
+    } else {
+        0.0
+    }
+}
+
+///
+///PolyBLAMP residual for a slope change of 1 per sample at phase 0.
+///
+#[inline]
+fn blamp(t: SampleType, dt: SampleType) -> SampleType {
+    if t < dt {
+        let x = 1.0 - (t / dt);
+        (x * x * x) / 6.0
+    } else if t > 1.0 - dt {
+        let x = 1.0 + ((t - 1.0) / dt);
+        (x * x * x) / 6.0
+    } else {
+        0.0
+    }
+}
+
+/***********************************************************************
+ * Oscillator
+ **********************************************************************/
+
+///
+///Band limited phase accumulator oscillator. Frequency is continuous 
+///and may change every sample. Output is -1..1 starting at phase 0 
+///where sine and triangle cross zero going up and saw, square and pulse
+///start high.
+///
+pub struct Oscillator {
+    wave:  Wave,
+    width: SampleType, //Pulse width 0..1.
+    rate:  SampleType, //Sample rate.
+    phase: Phasor,
+    table: [SampleType; TABLE_LEN + 1], //One sine cycle.
+}
+
+impl Default for Oscillator {
+    fn default() -> Self {
+        let mut table = [0.0; TABLE_LEN + 1];
+        for (i, val) in table.iter_mut().enumerate() {
+            *val = taylor(TAU * ((i % TABLE_LEN) as SampleType) / (TABLE_LEN as SampleType));
+        }
+
+        Oscillator {
+            wave:  Wave::Sine,
+            width: 0.5,
+            rate:  SAMPLE_RATE,
+            phase: Phasor::default(),
+            table,
+        }
+    }
+}
+
+impl Oscillator {
+    pub fn sample_rate(&mut self, rate: SampleType) -> &mut Self {
+        let new = self;
+        new.rate = rate;
+        new.phase.sample_rate(rate);
+        new
+    }
+
+    pub fn wave(&mut self, wave: Wave) -> &mut Self {
+        let new = self;
+        new.wave = wave;
+        new
+    }
+
+///
+///Fraction of the cycle the pulse wave is high.
+///
+    pub fn width(&mut self, width: SampleType) -> &mut Self {
+        let new = self;
+        new.width = width.clamp(0.0, 1.0);
+        new
+    }
+
+///
+///Jump to phase 0..1. Used for phase reset and hard sync.
+///
+    pub fn reset(&mut self, phase: SampleType) {
+        self.phase.reset(phase);
+    }
+
+    #[inline]
+    pub fn phase(&self) -> SampleType {
+        self.phase.phase()
+    }
+
+///
+///Output at the current phase then advance by freq Hz.
+///
+    #[inline]
+    pub fn next(&mut self, freq: SampleType) -> SampleType {
+        let dt = fabsf(freq / self.rate);
+        let dt = if dt > 0.5 { 0.5 } else { dt };
+        let t  = self.phase.next(freq);
+
+        match self.wave {
+            Wave::Sine => {
+//A tiny negative phase wraps to exactly 1.0. Keep the index in range.
+                let pos  = t * (TABLE_LEN as SampleType);
+                let idx  = pos as usize;
+                let idx  = if idx >= TABLE_LEN { TABLE_LEN - 1 } else { idx };
+                let frac = pos - (idx as SampleType);
+                let x0   = self.table[idx];
+                x0 + ((self.table[idx + 1] - x0) * frac)
+            },
+
+            Wave::Triangle => {
+//Corners at 0.25 (top) and 0.75 (bottom). Slope changes by 8 per cycle.
+                let naive = 1.0 - (4.0 * fabsf(wrap(t + 0.25) - 0.5));
+                naive + (8.0 * dt * (blamp(wrap(t + 0.25), dt) - blamp(wrap(t + 0.75), dt)))
+            },
+
+            Wave::Saw => {
+                let naive = (2.0 * t) - 1.0;
+                naive - blep(t, dt)
+            },
+
+            Wave::Square => self.pulse(t, dt, 0.5),
+
+            Wave::Pulse  => self.pulse(t, dt, self.width),
+        }
+    }
+
+    #[inline]
+    fn pulse(&self, t: SampleType, dt: SampleType, width: SampleType) -> SampleType {
+        let naive = if t < width { 1.0 } else { -1.0 };
+        naive + blep(t, dt) - blep(wrap(t - width), dt)
+    }
+}
+
+/***********************************************************************
+ * Osc
+ **********************************************************************/
+
+///
+///Oscillator effect. Process input (port 0) is hard sync: the phase 
+///restarts whenever it rises through zero, so another Osc connected 
+///there acts as master. Port 1 is phase reset: the phase restarts when
+///it rises above 0.5, for gates and footswitches.
+///
+pub struct Osc {
+    freq:   Smooth,     //Frequency in Hz.
+    scale:  Smooth,     //Output is scaled by this amount.
+    offset: Smooth,     //Then offset by this amount.
+    sync:   SampleType, //Last sync input.
+    trig:   SampleType, //Last reset input.
+    osc:    Oscillator,
+}
+
+impl Default for Osc {
+    fn default() -> Self {
+        let mut osc = Osc {
+            freq:   Smooth::new(440.0),
+            scale:  Smooth::new(1.0),
+            offset: Smooth::new(0.0),
+            sync:   0.0,
+            trig:   0.0,
+            osc:    Oscillator::default(),
+        };
+        osc.prepare(SAMPLE_RATE, 0);
+        osc
+    }
+}
+
+impl Effect for Osc {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in, 0.0)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in, 0.0);
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+
+///
+///Sync on port 0, reset on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let output = match outputs.first_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        match inputs {
+            [sync, reset, ..] => {
+                for ((smpl_out, smpl_sync), smpl_reset) in output.iter_mut()
+                                                                .zip(sync.iter())
+                                                                .zip(reset.iter())
+                {
+                    *smpl_out = self.tick(*smpl_sync, *smpl_reset);
+                }
+            },
+            [sync, ..] => self.process_block(sync, output),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq.reset(440.0);
+        self.scale.reset(1.0);
+        self.offset.reset(0.0);
+        self.osc.wave(Wave::Sine).width(0.5);
+        self.osc.reset(0.0);
+        self.sync = 0.0;
+        self.trig = 0.0;
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.osc.sample_rate(sample_rate);
+        self.freq.sample_rate(sample_rate);
+        self.scale.sample_rate(sample_rate);
+        self.offset.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.wave(Wave::from_param(val)); },
+            1 => { self.freq(val); },
+            2 => { self.width(val); },
+            3 => { self.scale(val); },
+            4 => { self.offset(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.osc.wave.to_param()),
+            1 => Ok(self.freq.target()),
+            2 => Ok(self.osc.width),
+            3 => Ok(self.scale.target()),
+            4 => Ok(self.offset.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Osc {
+    #[inline]
+    fn tick(&mut self, sync: SampleType, reset: SampleType) -> SampleType {
+        if (self.sync <= 0.0 && sync > 0.0) || (self.trig <= 0.5 && reset > 0.5) {
+            self.osc.reset(0.0);
+        }
+        self.sync = sync;
+        self.trig = reset;
+
+        (self.osc.next(self.freq.next()) * self.scale.next()) + self.offset.next()
+    }
+
+    pub fn wave(&mut self, wave: Wave) -> &mut Self {
+        let new = self;
+        new.osc.wave(wave);
+        new
+    }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq.set(hz);
+        new
+    }
+
+    pub fn width(&mut self, width: SampleType) -> &mut Self {
+        let new = self;
+        new.osc.width(width);
+        new
+    }
+
+    pub fn scale(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.scale.set(val);
+        new
+    }
+
+    pub fn offset(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.offset.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::osc::{Oscillator, Osc, Wave};
+    use crate::{Effect, SampleType};
+
+    fn cycle(wave: Wave, width: SampleType) -> (SampleType, SampleType, SampleType) {
+        let mut osc = Oscillator::default();
+        osc.wave(wave).width(width);
+        let (mut sum, mut min, mut max) = (0.0, 0.0, 0.0);
+        for _ in 0..48000 {
+            let v = osc.next(100.0);
+            sum += v;
+            min = if v < min { v } else { min };
+            max = if v > max { v } else { max };
+        }
+        (sum / 48000.0, min, max)
+    }
+
+    #[test]
+    fn waves() {
+        let mut osc = Oscillator::default();
+        for i in 0..4800 {
+            let expect = (2.0 * core::f32::consts::PI * 440.0 * (i as SampleType) / 48000.0).sin();
+            assert!((osc.next(440.0) - expect).abs() < 1e-3);
+        }
+
+        for wave in [Wave::Triangle, Wave::Saw, Wave::Square].iter() {
+            let (mean, min, max) = cycle(*wave, 0.5);
+            assert!(mean.abs() < 0.01);
+            assert!(min > -1.05 && max < 1.05);
+        }
+
+        let (mean, _, _) = cycle(Wave::Pulse, 0.25);
+        assert!((mean + 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn negative() {
+//Running backwards through phase 0 stays inside the sine table.
+        let mut osc = Oscillator::default();
+        for _ in 0..10 {
+            let val = osc.next(-0.00001);
+            assert!(val.abs() < 1e-3);
+        }
+        let mut osc = Oscillator::default();
+        for _ in 0..48000 {
+            assert!(osc.next(-440.0).abs() < 1.001);
+        }
+    }
+
+    #[test]
+    fn sync() {
+//A rising sync input restarts the phase.
+        let mut osc = Osc::default();
+        osc.wave(Wave::Saw).freq(100.0);
+        for _ in 0..200 {
+            osc.process(-1.0);
+        }
+        assert!(osc.osc.phase() > 0.2);
+        osc.process(1.0);
+        assert!(osc.osc.phase() < 0.01);
+    }
+}
@@ -0,0 +1,310 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use libm::{cos, sin};
+
+///
+///Half the number of non-zero side taps in each halfband filter. The
+///filters are 4 * HALF_TAPS - 1 long with the odd offsets from center
+///zero so each polyphase branch is either HALF_TAPS * 2 taps or a pure
+///delay.
+///
+const HALF_TAPS: usize = 8;
+const TAPS:      usize = HALF_TAPS * 2;
+
+///
+///Highest oversampling factor and number of 2x stages to get there.
+///
+pub const MAX_FACTOR: usize = 8;
+const MAX_STAGES:     usize = 3;
+
+/***********************************************************************
+ * Halfband
+ **********************************************************************/
+
+///
+///Blackman windowed halfband lowpass. Returns the taps at even indexes
+///(odd offsets from center) scaled so they sum to 0.5; the center tap 
+///is 0.5 and every other tap is zero.
+///
+fn halfband() -> [SampleType; TAPS] {
+    let len    = (4 * HALF_TAPS) - 1;
+    let center = (len - 1) / 2;
+    let pi     = core::f64::consts::PI;
+    let mut taps = [0.0; TAPS];
+    let mut sum  = 0.0;
+
+    for (i, tap) in taps.iter_mut().enumerate() {
+        let n = 2 * i;
+        let d = (n as f64) - (center as f64);
+        let sinc = sin(pi * d / 2.0) / (pi * d);
+        let w = 0.42 - (0.5 * cos(2.0 * pi * (n as f64) / ((len - 1) as f64)))
+                     + (0.08 * cos(4.0 * pi * (n as f64) / ((len - 1) as f64)));
+        *tap = (sinc * w) as SampleType;
+        sum += *tap;
+    }
+
+    for tap in taps.iter_mut() {
+        *tap *= 0.5 / sum;
+    }
+
+    taps
+}
+
+/***********************************************************************
+ * Stage
+ **********************************************************************/
+
+///
+///One 2x up and down stage. Histories are rings written backwards so
+///the newest sample is at pos.
+///
+#[derive(Clone, Copy)]
+struct Stage {
+    up:       [SampleType; TAPS],      //Input history.
+    up_pos:   usize,
+    even:     [SampleType; TAPS],      //Even oversampled history.
+    odd:      [SampleType; HALF_TAPS + 1], //Odd oversampled history.
+    down_pos: usize,
+    odd_pos:  usize,
+}
+
+impl Default for Stage {
+    fn default() -> Self {
+        Stage {
+            up:       [0.0; TAPS],
+            up_pos:   0,
+            even:     [0.0; TAPS],
+            down_pos: 0,
+            odd:      [0.0; HALF_TAPS + 1],
+            odd_pos:  0,
+        }
+    }
+}
+
+impl Stage {
+///
+///Two output samples for one input.
+///
+    #[inline]
+    fn up(&mut self, h: &[SampleType; TAPS], smpl: SampleType) -> (SampleType, SampleType) {
+        self.up_pos = (self.up_pos + TAPS - 1) % TAPS;
+        self.up[self.up_pos] = smpl;
+
+        let mut even = 0.0;
+        for (i, tap) in h.iter().enumerate() {
+            even += tap * self.up[(self.up_pos + i) % TAPS];
+        }
+
+        let odd = self.up[(self.up_pos + HALF_TAPS - 1) % TAPS];
+        (2.0 * even, odd)
+    }
+
+///
+///One output sample for two inputs.
+///
+    #[inline]
+    fn down(&mut self, h: &[SampleType; TAPS], smpl_even: SampleType, smpl_odd: SampleType) -> SampleType {
+        self.down_pos = (self.down_pos + TAPS - 1) % TAPS;
+        self.even[self.down_pos] = smpl_even;
+
+        let mut acc = 0.0;
+        for (i, tap) in h.iter().enumerate() {
+            acc += tap * self.even[(self.down_pos + i) % TAPS];
+        }
+
+        let len = HALF_TAPS + 1;
+        let delayed = self.odd[(self.odd_pos + HALF_TAPS - 1) % len];
+        self.odd_pos = (self.odd_pos + len - 1) % len;
+        self.odd[self.odd_pos] = smpl_odd;
+
+        acc + (0.5 * delayed)
+    }
+}
+
+/***********************************************************************
+ * Oversample
+ **********************************************************************/
+
+///
+///Runs any effect at 2x, 4x or 8x the sample rate. Input is upsampled
+///and output downsampled through cascaded polyphase halfband stages.
+///The wrapped effect is prepared for the oversampled rate and its
+///parameters are passed through unchanged.
+///
+///Only port 0 is oversampled.
+///
+pub struct Oversample<E: Effect> {
+    inner:  E,
+    factor: usize, //1, 2, 4 or 8.
+    stages: usize, //log2(factor).
+    rate:   SampleType,
+    block:  usize,
+    h:      [SampleType; TAPS],
+    stage:  [Stage; MAX_STAGES],
+}
+
+impl<E: Effect> Oversample<E> {
+///
+///Wrap inner oversampled by factor. See factor().
+///
+    pub fn new(inner: E, factor: usize) -> Self {
+        let mut os = Oversample {
+            inner,
+            factor: 1,
+            stages: 0,
+            rate:   SAMPLE_RATE,
+            block:  0,
+            h:      halfband(),
+            stage:  [Stage::default(); MAX_STAGES],
+        };
+        os.factor(factor);
+        os
+    }
+
+///
+///Oversampling factor rounded down to 1, 2, 4 or 8. Changing the 
+///factor re-prepares the wrapped effect and clears filter history.
+///
+    pub fn factor(&mut self, factor: usize) -> &mut Self {
+        let new = self;
+        let factor = if factor >= 8 { 8 } else if factor >= 4 { 4 } else if factor >= 2 { 2 } else { 1 };
+
+        if factor != new.factor {
+            new.factor = factor;
+            new.stages = factor.trailing_zeros() as usize;
+            new.stage  = [Stage::default(); MAX_STAGES];
+            let (rate, block) = (new.rate, new.block);
+            new.inner.prepare(rate * (factor as SampleType), block * factor);
+        }
+
+        new
+    }
+
+    pub fn get_factor(&self) -> usize {
+        self.factor
+    }
+
+///
+///The wrapped effect.
+///
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.inner
+    }
+
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let mut buf = [0.0; MAX_FACTOR];
+        let mut tmp = [0.0; MAX_FACTOR];
+        buf[0] = smpl_in;
+
+        let mut len = 1;
+        for stage in self.stage[..self.stages].iter_mut() {
+            for i in 0..len {
+                let (even, odd) = stage.up(&self.h, buf[i]);
+                tmp[2 * i] = even;
+                tmp[(2 * i) + 1] = odd;
+            }
+            len *= 2;
+            buf[..len].copy_from_slice(&tmp[..len]);
+        }
+
+        for smpl in buf[..len].iter_mut() {
+            *smpl = self.inner.process(*smpl);
+        }
+
+        for stage in self.stage[..self.stages].iter_mut().rev() {
+            len /= 2;
+            for i in 0..len {
+                buf[i] = stage.down(&self.h, buf[2 * i], buf[(2 * i) + 1]);
+            }
+        }
+
+        buf[0]
+    }
+}
+
+impl<E: Effect> Effect for Oversample<E> {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.stage = [Stage::default(); MAX_STAGES];
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, max_block: usize) {
+        self.rate  = sample_rate;
+        self.block = max_block;
+        self.inner.prepare(sample_rate * (self.factor as SampleType), max_block * self.factor);
+    }
+
+    fn params(&mut self) -> &'static [param::Desc] { self.inner.params() }
+    fn num_params(&mut self) -> usize { self.inner.num_params() }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), param::ERROR> {
+        self.inner.set_param(idx, val)
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, param::ERROR> {
+        self.inner.get_param(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::oversample::Oversample;
+    use crate::thru::Thru;
+    use crate::Effect;
+
+    fn tone(os: &mut Oversample<Thru>, freq: f32) -> f32 {
+        let mut peak: f32 = 0.0;
+        for n in 0..4800 {
+            let x = (2.0 * std::f32::consts::PI * freq * n as f32 / 48000.0).sin();
+            let y = os.process(x);
+            if n > 2400 { peak = peak.max(y.abs()); }
+        }
+        peak
+    }
+
+    #[test]
+    fn passband() {
+//Unity in the passband at every factor, including through a change.
+        for &f in [2, 4, 8, 1].iter() {
+            let mut os = Oversample::new(Thru, f);
+            let peak = tone(&mut os, 1000.0);
+            assert!((peak - 1.0).abs() < 0.01);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::SampleType;
+use libm::{expf, logf, roundf};
+
+/***********************************************************************
+ * ERROR
+ **********************************************************************/
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ERROR {
+    INDEX,     //No parameter at index.
+    RANGE,     //Value is outside of min..max.
+    NONFINITE, //Value is NaN or infinite.
+}
+
+impl ERROR {
+    pub fn msg (&self) -> &'static str {
+        match self {
+            ERROR::INDEX => "Parameter doesn't exist.",
+            ERROR::RANGE => "Parameter value is out of range.",
+            ERROR::NONFINITE => "Parameter value is not a finite number."
+        }
+    }
+}
+
+///
+///Verify idx refers to one of params and val is acceptable for it.
+///Returns val on success.
+///
+pub fn check(params: &[Desc], idx: usize, val: SampleType) -> Result<SampleType, ERROR> {
+    match params.get(idx) {
+        Some(desc) => {
+            if !val.is_finite() {
+                Err(ERROR::NONFINITE)
+            } else if val < desc.min || val > desc.max {
+                Err(ERROR::RANGE)
+            } else {
+                Ok(val)
+            }
+        }
+        None => Err(ERROR::INDEX)
+    }
+}
+
+/***********************************************************************
+ * Unit
+ **********************************************************************/
+
+///
+///Unit a parameter value is expressed in.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unit {
+    None,    //Plain number. Signal level, offset, index.
+    Seconds, //Time.
+    Hz,      //Frequency.
+    Ratio,   //Fraction 0..1. Feedback, duty cycle, mix.
+    Db,      //Decibels.
+}
+
+/***********************************************************************
+ * Curve
+ **********************************************************************/
+
+///
+///How a parameter's values are spread across a control's travel.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,  //Equal travel for equal differences.
+    Log,     //Equal travel for equal ratios. Requires min > 0.
+    Stepped, //Whole numbers only. Modes, counts.
+}
+
+/***********************************************************************
+ * Desc
+ **********************************************************************/
+
+///
+///Describes a single effect parameter.
+///
+#[derive(Clone, Copy)]
+pub struct Desc {
+    pub name:    &'static str, //Short human readable name.
+    pub min:     SampleType,   //Smallest accepted value.
+    pub max:     SampleType,   //Largest accepted value.
+    pub default: SampleType,   //Value after reset().
+    pub unit:    Unit,         //Unit value is expressed in.
+    pub curve:   Curve,        //Scaling curve.
+}
+
+impl Desc {
+///
+///Limit value to min..max.
+///
+    pub fn clamp(&self, val: SampleType) -> SampleType {
+        if val < self.min {
+            self.min
+        } else if val > self.max {
+            self.max
+        } else {
+            val
+        }
+    }
+
+///
+///Map a value in min..max to 0..1 along the curve.
+///
+    pub fn to_normal(&self, val: SampleType) -> SampleType {
+        let val = self.clamp(val);
+
+        if self.max <= self.min {
+            return 0.0;
+        }
+
+        match self.curve {
+            Curve::Linear  => (val - self.min) / (self.max - self.min),
+            Curve::Log     => logf(val / self.min) / logf(self.max / self.min),
+            Curve::Stepped => (roundf(val) - self.min) / (self.max - self.min),
+        }
+    }
+
+///
+///Map 0..1 to a value in min..max along the curve.
+///
+    pub fn from_normal(&self, norm: SampleType) -> SampleType {
+        let norm = norm.clamp(0.0, 1.0);
+
+        match self.curve {
+            Curve::Linear  => self.min + (norm * (self.max - self.min)),
+            Curve::Log     => self.min * expf(norm * logf(self.max / self.min)),
+            Curve::Stepped => roundf(self.min + (norm * (self.max - self.min))),
+        }
+    }
+}
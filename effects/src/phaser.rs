@@ -0,0 +1,402 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, SHAPE_MAX, wrap};
+use libm::{exp2f, roundf, tanf};
+use core::f32::consts::PI;
+
+///
+///Selectable numbers of allpass stages.
+///
+const STAGES: [usize; 5] = [2, 4, 6, 8, 12];
+const MAX_STAGES: usize = 12;
+
+static PARAMS: [Desc; 10] = [
+    Desc { name: "stages",   min: 2.0,   max: 12.0,      default: 4.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "rate",     min: 0.01,  max: 10.0,      default: 0.5,   unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "depth",    min: 0.0,   max: 4.0,       default: 2.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "center",   min: 50.0,  max: 5000.0,    default: 500.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "feedback", min: -0.95, max: 0.95,      default: 0.5,   unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "offset",   min: 0.0,   max: 1.0,       default: 0.25,  unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "mix",      min: 0.0,   max: 1.0,       default: 0.5,   unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "shape",    min: 0.0,   max: SHAPE_MAX, default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "source",   min: 0.0,   max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "sweep",    min: -1.0,  max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Channel
+ **********************************************************************/
+
+///
+///Allpass chain and feedback memory for one channel.
+///
+#[derive(Default)]
+struct Channel {
+    z:  [SampleType; MAX_STAGES], //First order allpass state.
+    fb: SampleType,               //Last chain output.
+}
+
+impl Channel {
+    #[inline]
+    fn tick(&mut self, 
+            smpl_in: SampleType, 
+            a: SampleType, 
+            stages: usize, 
+            feedback: SampleType) -> SampleType 
+    {
+        let mut smpl = smpl_in + (self.fb * feedback);
+
+        for z in self.z[..stages].iter_mut() {
+            let y = (a * smpl) + *z;
+            *z = smpl - (a * y);
+            smpl = y;
+        }
+
+        if !smpl.is_finite() {
+            self.clear();
+            smpl = 0.0;
+        }
+
+        self.fb = smpl;
+        smpl
+    }
+
+    fn clear(&mut self) {
+        *self = Channel::default();
+    }
+}
+
+/***********************************************************************
+ * Phaser
+ **********************************************************************/
+
+///
+///Phaser. Allpass stages swept around center by depth octaves. Stereo
+///on ports 0 and 1 with the right LFO offset by a fraction of a cycle.
+///
+///With source set to 1 the internal LFO is replaced by the sweep 
+///parameter (-1..1) so a rack connection from a Sine or Pwm can drive
+///both channels.
+///
+pub struct Phaser {
+    stages:   usize,      //Allpass stages per channel.
+    freq:     SampleType, //LFO rate in Hz.
+    depth:    Smooth,     //Sweep either side of center in octaves.
+    center:   Smooth,     //Center frequency in Hz.
+    feedback: Smooth,     //Output fed back to the chain input.
+    offset:   SampleType, //Right LFO phase offset in cycles.
+    mix:      Smooth,     //0 dry .. 1 wet.
+    shape:    Shape,      //LFO waveform.
+    external: bool,       //Sweep parameter replaces the LFO.
+    sweep:    SampleType, //External sweep -1..1.
+    rate:     SampleType, //Sample rate.
+    lfo:      Phasor,
+    left:     Channel,
+    right:    Channel,
+}
+
+impl Default for Phaser {
+    fn default() -> Self {
+        let mut phaser = Phaser {
+            stages:   4,
+            freq:     0.5,
+            depth:    Smooth::new(2.0),
+            center:   Smooth::new(500.0),
+            feedback: Smooth::new(0.5),
+            offset:   0.25,
+            mix:      Smooth::new(0.5),
+            shape:    Shape::Sine,
+            external: false,
+            sweep:    0.0,
+            rate:     SAMPLE_RATE,
+            lfo:      Phasor::default(),
+            left:     Channel::default(),
+            right:    Channel::default(),
+        };
+        phaser.reset();
+        phaser
+    }
+}
+
+impl Effect for Phaser {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in, 0.0).0
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in, 0.0).0;
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+    fn num_outputs(&mut self) -> usize { 2 }
+
+///
+///Left on port 0, right on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        match (inputs, outputs) {
+            ([in_l, in_r, ..], [out_l, out_r, ..]) => {
+                for (((smpl_l, smpl_r), l), r) in out_l.iter_mut()
+                                                       .zip(out_r.iter_mut())
+                                                       .zip(in_l.iter())
+                                                       .zip(in_r.iter())
+                {
+                    let (ol, or) = self.tick(*l, *r);
+                    *smpl_l = ol;
+                    *smpl_r = or;
+                }
+            },
+            ([input, ..], [output, ..]) => self.process_block(input, output),
+            _ => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.stages = 4;
+        self.freq = 0.5;
+        self.depth.reset(2.0);
+        self.center.reset(500.0);
+        self.feedback.reset(0.5);
+        self.offset = 0.25;
+        self.mix.reset(0.5);
+        self.shape = Shape::Sine;
+        self.external = false;
+        self.sweep = 0.0;
+        self.lfo.reset(0.0);
+        self.left.clear();
+        self.right.clear();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.lfo.sample_rate(sample_rate);
+        self.depth.sample_rate(sample_rate);
+        self.center.sample_rate(sample_rate);
+        self.feedback.sample_rate(sample_rate);
+        self.mix.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.stages(roundf(val) as usize); },
+            1 => { self.freq(val); },
+            2 => { self.depth(val); },
+            3 => { self.center(val); },
+            4 => { self.feedback(val); },
+            5 => { self.offset(val); },
+            6 => { self.mix(val); },
+            7 => { self.shape(Shape::from_param(val)); },
+            8 => { self.external(val >= 0.5); },
+            9 => { self.sweep(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.stages as SampleType),
+            1 => Ok(self.freq),
+            2 => Ok(self.depth.target()),
+            3 => Ok(self.center.target()),
+            4 => Ok(self.feedback.target()),
+            5 => Ok(self.offset),
+            6 => Ok(self.mix.target()),
+            7 => Ok(self.shape.to_param()),
+            8 => Ok(if self.external { 1.0 } else { 0.0 }),
+            9 => Ok(self.sweep),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Phaser {
+///
+///Process a stereo pair.
+///
+    #[inline]
+    fn tick(&mut self, smpl_l: SampleType, smpl_r: SampleType) -> (SampleType, SampleType) {
+        let phase    = self.lfo.next(self.freq);
+        let depth    = self.depth.next();
+        let center   = self.center.next();
+        let feedback = self.feedback.next();
+        let mix      = self.mix.next();
+
+        let (mod_l, mod_r) = if self.external {
+            (self.sweep, self.sweep)
+        } else {
+            (self.shape.value(phase), self.shape.value(wrap(phase + self.offset)))
+        };
+
+        let a_l = self.coef(center * exp2f(depth * mod_l));
+        let a_r = self.coef(center * exp2f(depth * mod_r));
+
+        let wet_l = self.left.tick(smpl_l, a_l, self.stages, feedback);
+        let wet_r = self.right.tick(smpl_r, a_r, self.stages, feedback);
+
+        (
+            (smpl_l * (1.0 - mix)) + (wet_l * mix),
+            (smpl_r * (1.0 - mix)) + (wet_r * mix)
+        )
+    }
+
+///
+///First order allpass coefficient for a 90 degree shift at freq.
+///
+    #[inline]
+    fn coef(&self, freq: SampleType) -> SampleType {
+        let max  = self.rate * 0.45;
+        let freq = if freq < 20.0 { 20.0 } else if freq > max { max } else { freq };
+        let t = tanf(PI * freq / self.rate);
+        (t - 1.0) / (t + 1.0)
+    }
+
+///
+///Number of stages. Rounded to the nearest of 2, 4, 6, 8 or 12.
+///
+    pub fn stages(&mut self, stages: usize) -> &mut Self {
+        let new = self;
+        let mut best = STAGES[0];
+        for &s in STAGES.iter() {
+            if (s as isize - stages as isize).abs() < (best as isize - stages as isize).abs() {
+                best = s;
+            }
+        }
+        new.stages = best;
+        new
+    }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq = hz;
+        new
+    }
+
+///
+///Sweep either side of center in octaves.
+///
+    pub fn depth(&mut self, oct: SampleType) -> &mut Self {
+        let new = self;
+        new.depth.set(oct);
+        new
+    }
+
+    pub fn center(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.center.set(hz);
+        new
+    }
+
+    pub fn feedback(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.feedback.set(val);
+        new
+    }
+
+///
+///Right channel LFO offset in cycles. 0.5 sweeps the channels in 
+///opposite directions.
+///
+    pub fn offset(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.offset = wrap(val);
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+
+    pub fn shape(&mut self, shape: Shape) -> &mut Self {
+        let new = self;
+        new.shape = shape;
+        new
+    }
+
+///
+///Use the sweep parameter instead of the internal LFO.
+///
+    pub fn external(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.external = on;
+        new
+    }
+
+///
+///External sweep -1..1. Only used when external is on.
+///
+    pub fn sweep(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.sweep = val;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::phaser::Phaser;
+    use crate::Effect;
+
+    #[test]
+    fn stages() {
+        let mut ph = Phaser::default();
+        for &(val, stages) in [(2.0, 2.0), (3.9, 4.0), (5.0, 4.0), (9.0, 8.0), (11.0, 12.0)].iter() {
+            assert!(ph.set_param(0, val).is_ok());
+            assert!(ph.get_param(0).ok() == Some(stages));
+        }
+    }
+
+    #[test]
+    fn stereo() {
+//Offset of half a cycle on a centered sweep sends the channels apart.
+        let mut ph = Phaser::default();
+        ph.offset(0.5).mix(1.0).feedback(0.0);
+        let audio = [1.0; 2000];
+        let (mut l, mut r) = ([0.0; 2000], [0.0; 2000]);
+        ph.process_ports(&[&audio, &audio], &mut [&mut l, &mut r]);
+        assert!(l.iter().zip(r.iter()).any(|(a, b)| (a - b).abs() > 0.01));
+
+//External sweep drives both channels the same.
+        ph.reset();
+        ph.external(true).sweep(0.5);
+        ph.process_ports(&[&audio, &audio], &mut [&mut l, &mut r]);
+        assert!(l.iter().zip(r.iter()).all(|(a, b)| a == b));
+    }
+}
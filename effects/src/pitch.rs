@@ -0,0 +1,472 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::delayline::{DelayLine, Interp};
+use crate::detector::Detector;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use libm::{cosf, expf, exp2f, fabsf};
+use core::f32::consts::PI;
+
+///
+///Window bounds in seconds. Longer windows are smoother on sustained
+///notes, shorter ones on percussive material.
+///
+const MIN_WINDOW_SECONDS: SampleType = 0.01;
+const MAX_WINDOW_SECONDS: SampleType = 0.1;
+
+/***********************************************************************
+ * Shifter
+ **********************************************************************/
+
+///
+///Pitch shift primitive. Two taps sweep through a delay line at the
+///rate needed for the pitch ratio, half a window apart, each faded out
+///with a raised cosine as it wraps round. The taps' gains always sum 
+///to one.
+///
+pub struct Shifter {
+    line:   DelayLine,
+    window: SampleType, //Sweep length in samples.
+    ratio:  SampleType, //Output pitch / input pitch.
+    phase:  SampleType, //First tap position 0..1.
+}
+
+impl Shifter {
+///
+///New shifter able to use windows up to max samples.
+///
+    pub fn new(max: usize) -> Self {
+        let mut line = DelayLine::new(max + 4);
+        line.interp(Interp::Hermite);
+
+        Shifter {
+            line,
+            window: max as SampleType,
+            ratio:  1.0,
+            phase:  0.0,
+        }
+    }
+
+///
+///Pitch ratio. 2 is an octave up, 0.5 an octave down.
+///
+    pub fn ratio(&mut self, ratio: SampleType) -> &mut Self {
+        let new = self;
+        new.ratio = ratio;
+        new
+    }
+
+///
+///Window in samples, limited to the size given to new().
+///
+    pub fn window(&mut self, len: SampleType) -> &mut Self {
+        let new = self;
+        let max = (new.line.max() - 4) as SampleType;
+        new.window = if len < 4.0 { 4.0 } else if len > max { max } else { len };
+        new
+    }
+
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.phase = 0.0;
+    }
+
+    #[inline]
+    pub fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        self.line.write(smpl_in);
+
+        let p1 = self.phase;
+        let p2 = if p1 >= 0.5 { p1 - 0.5 } else { p1 + 0.5 };
+
+        let g1 = 0.5 - (0.5 * cosf(2.0 * PI * p1));
+        let g2 = 1.0 - g1;
+
+        let out = (self.line.read(2.0 + (p1 * self.window)) * g1)
+                + (self.line.read(2.0 + (p2 * self.window)) * g2);
+
+//Delay shrinks when shifting up, grows when shifting down.
+        self.phase += (1.0 - self.ratio) / self.window;
+        self.phase -= (self.phase as i32) as SampleType;
+        if self.phase < 0.0 {
+            self.phase += 1.0;
+        }
+
+        out
+    }
+}
+
+/***********************************************************************
+ * PitchShift
+ **********************************************************************/
+
+static PITCH_PARAMS: [Desc; 4] = [
+    Desc { name: "semitones", min: -24.0, max: 24.0, default: 0.0, unit: Unit::None, curve: Curve::Stepped },
+    Desc { name: "cents",     min: -100.0, max: 100.0, default: 0.0, unit: Unit::None, curve: Curve::Linear },
+    Desc { name: "window",    min: MIN_WINDOW_SECONDS, max: MAX_WINDOW_SECONDS, default: 0.05, unit: Unit::Seconds, curve: Curve::Log },
+    Desc { name: "mix",       min: 0.0, max: 1.0, default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+///
+///Pitch shifter -24..+24 semitones with fine tuning in cents.
+///
+pub struct PitchShift {
+    semitones: SampleType,
+    cents:     SampleType,
+    window:    SampleType, //Seconds.
+    mix:       Smooth,
+    rate:      SampleType, //Sample rate.
+    shifter:   Shifter,
+}
+
+impl Default for PitchShift {
+    fn default() -> Self {
+        let mut ps = PitchShift {
+            semitones: 0.0,
+            cents:     0.0,
+            window:    0.05,
+            mix:       Smooth::new(1.0),
+            rate:      SAMPLE_RATE,
+            shifter:   Shifter::new(4),
+        };
+        ps.prepare(SAMPLE_RATE, 0);
+        ps
+    }
+}
+
+impl Effect for PitchShift {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.semitones = 0.0;
+        self.cents = 0.0;
+        self.update();
+        self.window(0.05);
+        self.mix.reset(1.0);
+        self.shifter.clear();
+    }
+
+///
+///Line is sized for the longest window at the sample rate.
+///
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate = sample_rate;
+        self.shifter = Shifter::new((MAX_WINDOW_SECONDS * sample_rate) as usize);
+        self.mix.sample_rate(sample_rate);
+        self.update();
+        let window = self.window;
+        self.window(window);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PITCH_PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PITCH_PARAMS, idx, val)?;
+        match idx {
+            0 => { self.semitones(val); },
+            1 => { self.cents(val); },
+            2 => { self.window(val); },
+            3 => { self.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.semitones),
+            1 => Ok(self.cents),
+            2 => Ok(self.window),
+            3 => Ok(self.mix.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl PitchShift {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let wet = self.shifter.tick(smpl_in);
+        let mix = self.mix.next();
+        (smpl_in * (1.0 - mix)) + (wet * mix)
+    }
+
+    fn update(&mut self) {
+        let semis = self.semitones + (self.cents / 100.0);
+        self.shifter.ratio(exp2f(semis / 12.0));
+    }
+
+    pub fn semitones(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.semitones = val;
+        new.update();
+        new
+    }
+
+    pub fn cents(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.cents = val;
+        new.update();
+        new
+    }
+
+///
+///Window in seconds.
+///
+    pub fn window(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.window = sec;
+        let len = sec * new.rate;
+        new.shifter.window(len);
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+}
+
+/***********************************************************************
+ * Octaver
+ **********************************************************************/
+
+static OCTAVER_PARAMS: [Desc; 3] = [
+    Desc { name: "sub", min: 0.0, max: 1.0, default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "up",  min: 0.0, max: 1.0, default: 0.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "dry", min: 0.0, max: 1.0, default: 1.0, unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+///
+///Lowpass corner for tracking and for smoothing the sub square.
+///
+const TRACK_HZ: SampleType = 800.0;
+const SUB_HZ:   SampleType = 1200.0;
+const DC_HZ:    SampleType = 10.0;
+
+///
+///Analog style octaver. A flip-flop toggled on each rising zero 
+///crossing gives a square an octave down, shaped by the input level.
+///Full wave rectifying the input gives an octave up. Works best on
+///single notes.
+///
+pub struct Octaver {
+    sub:      Smooth,
+    up:       Smooth,
+    dry:      Smooth,
+
+    track:    SampleType, //Tracking lowpass coefficient.
+    track_z:  SampleType,
+    smooth:   SampleType, //Sub lowpass coefficient.
+    sub_z:    SampleType,
+    dc:       SampleType, //Rectifier DC blocker coefficient.
+    dc_x:     SampleType,
+    dc_y:     SampleType,
+    high:     bool,       //Tracked signal above the upper threshold.
+    flip:     SampleType, //Flip-flop output +/-1.
+    detector: Detector,   //Input level.
+}
+
+impl Default for Octaver {
+    fn default() -> Self {
+        let mut oct = Octaver {
+            sub:      Smooth::new(1.0),
+            up:       Smooth::new(0.0),
+            dry:      Smooth::new(1.0),
+            track:    0.0,
+            track_z:  0.0,
+            smooth:   0.0,
+            sub_z:    0.0,
+            dc:       0.0,
+            dc_x:     0.0,
+            dc_y:     0.0,
+            high:     false,
+            flip:     1.0,
+            detector: Detector::default(),
+        };
+        oct.detector.attack(0.001).release(0.05);
+        oct.prepare(SAMPLE_RATE, 0);
+        oct
+    }
+}
+
+impl Effect for Octaver {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in)
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sub.reset(1.0);
+        self.up.reset(0.0);
+        self.dry.reset(1.0);
+        self.track_z = 0.0;
+        self.sub_z = 0.0;
+        self.dc_x = 0.0;
+        self.dc_y = 0.0;
+        self.high = false;
+        self.flip = 1.0;
+        self.detector.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.sub.sample_rate(sample_rate);
+        self.up.sample_rate(sample_rate);
+        self.dry.sample_rate(sample_rate);
+        self.detector.sample_rate(sample_rate);
+        self.track  = expf(-2.0 * PI * TRACK_HZ / sample_rate);
+        self.smooth = expf(-2.0 * PI * SUB_HZ / sample_rate);
+        self.dc     = 1.0 - (2.0 * PI * DC_HZ / sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &OCTAVER_PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&OCTAVER_PARAMS, idx, val)?;
+        match idx {
+            0 => { self.sub(val); },
+            1 => { self.up(val); },
+            2 => { self.dry(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.sub.target()),
+            1 => Ok(self.up.target()),
+            2 => Ok(self.dry.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Octaver {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let level = self.detector.next(smpl_in);
+
+//Track with hysteresis relative to level so noise doesn't toggle.
+        self.track_z = smpl_in + (self.track * (self.track_z - smpl_in));
+        let hyst = level * 0.1;
+        if !self.high && self.track_z > hyst {
+            self.high = true;
+            self.flip = -self.flip;
+        } else if self.high && self.track_z < -hyst {
+            self.high = false;
+        }
+
+        let sub = self.flip * level;
+        self.sub_z = sub + (self.smooth * (self.sub_z - sub));
+
+        let rect = fabsf(smpl_in);
+        self.dc_y = rect - self.dc_x + (self.dc * self.dc_y);
+        self.dc_x = rect;
+
+        (self.sub_z * self.sub.next()) + (self.dc_y * self.up.next()) + (smpl_in * self.dry.next())
+    }
+
+///
+///Octave down level.
+///
+    pub fn sub(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.sub.set(val);
+        new
+    }
+
+///
+///Octave up level.
+///
+    pub fn up(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.up.set(val);
+        new
+    }
+
+    pub fn dry(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.dry.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pitch::{PitchShift, Octaver};
+    use crate::Effect;
+
+    fn crossings(out: &[f32]) -> usize {
+        out.windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count()
+    }
+
+    fn sine(freq: f32, n: usize) -> Vec<f32> {
+        (0..n).map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / 48000.0).sin()).collect()
+    }
+
+    #[test]
+    fn shift() {
+//200Hz for 0.9s shifted an octave either way.
+        let input = sine(200.0, 48000);
+        let mut ps = PitchShift::default();
+        ps.semitones(12.0);
+        let out: Vec<f32> = input.iter().map(|x| ps.process(*x)).collect();
+        let n = crossings(&out[4800..]);
+        assert!(n > 350 && n < 370);
+
+        ps.semitones(-12.0);
+        let out: Vec<f32> = input.iter().map(|x| ps.process(*x)).collect();
+        let n = crossings(&out[4800..]);
+        assert!(n > 85 && n < 95);
+    }
+
+    #[test]
+    fn octaver() {
+//Sub octave alone.
+        let input = sine(200.0, 48000);
+        let mut oct = Octaver::default();
+        oct.dry(0.0);
+        let out: Vec<f32> = input.iter().map(|x| oct.process(*x)).collect();
+        let n = crossings(&out[4800..]);
+        assert!(n > 85 && n < 95);
+    }
+}
@@ -0,0 +1,509 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::delayline::{DelayLine, Interp};
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, wrap};
+
+///
+///Lengths and taps from Dattorro's "Effect Design Part 1" are in 
+///samples at 29761Hz and scaled to the sample rate.
+///
+const TUNING_RATE:   SampleType = 29761.0;
+const INPUT_AP:      [usize; 4] = [142, 107, 379, 277];
+const TANK_AP1:      [usize; 2] = [672, 908];   //Modulated, left/right.
+const TANK_DELAY1:   [usize; 2] = [4453, 4217];
+const TANK_AP2:      [usize; 2] = [1800, 2656];
+const TANK_DELAY2:   [usize; 2] = [3720, 3163];
+const MAX_EXCURSION: SampleType = 32.0;
+
+///
+///Output taps per side: delay1 (x2), ap2, delay2 from the opposite 
+///half then delay1, ap2, delay2 from the same half.
+///
+const TAPS: [[usize; 7]; 2] = [
+    [266, 2974, 1913, 1996, 1990, 187, 1066],
+    [353, 3627, 1228, 2673, 2111, 335, 121],
+];
+
+const OUTPUT_GAIN: SampleType = 0.6;
+const PREDELAY_SECONDS: SampleType = 0.5;
+
+static PARAMS: [Desc; 11] = [
+    Desc { name: "predelay",  min: 0.0, max: PREDELAY_SECONDS, default: 0.0,    unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "bandwidth", min: 0.0, max: 1.0,              default: 0.9995, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "indiff1",   min: 0.0, max: 0.95,             default: 0.75,   unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "indiff2",   min: 0.0, max: 0.95,             default: 0.625,  unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "decaydiff", min: 0.0, max: 0.95,             default: 0.7,    unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "decay",     min: 0.0, max: 0.99,             default: 0.5,    unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "damping",   min: 0.0, max: 1.0,              default: 0.0005, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "modrate",   min: 0.1, max: 5.0,              default: 1.0,    unit: Unit::Hz,      curve: Curve::Log },
+    Desc { name: "moddepth",  min: 0.0, max: 1.0,              default: 0.5,    unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "wet",       min: 0.0, max: 1.0,              default: 1.0,    unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "dry",       min: 0.0, max: 1.0,              default: 0.0,    unit: Unit::Ratio,   curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Diffuser
+ **********************************************************************/
+
+///
+///Allpass on a delay line so the tank can tap into it.
+///
+struct Diffuser {
+    line: DelayLine,
+    len:  usize,
+}
+
+impl Diffuser {
+    fn new(len: usize, excursion: usize) -> Self {
+        let mut line = DelayLine::new(len + excursion + 2);
+        line.interp(Interp::Linear);
+        Diffuser { line, len }
+    }
+
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType, g: SampleType) -> SampleType {
+        let d = self.line.tap(self.len);
+        self.diffuse(smpl_in, d, g)
+    }
+
+///
+///Tick with the length moved by excursion samples.
+///
+    #[inline]
+    fn tick_mod(&mut self, smpl_in: SampleType, g: SampleType, excursion: SampleType) -> SampleType {
+        let d = self.line.read((self.len as SampleType) + excursion);
+        self.diffuse(smpl_in, d, g)
+    }
+
+    #[inline]
+    fn diffuse(&mut self, smpl_in: SampleType, d: SampleType, g: SampleType) -> SampleType {
+        let w = smpl_in - (g * d);
+        self.line.write(w);
+        d + (g * w)
+    }
+}
+
+/***********************************************************************
+ * Tank
+ **********************************************************************/
+
+///
+///One half of the figure eight tank.
+///
+struct Tank {
+    ap1:    Diffuser,   //Modulated decay diffuser.
+    delay1: DelayLine,
+    ap2:    Diffuser,   //Decay diffuser.
+    delay2: DelayLine,
+    len1:   usize,
+    len2:   usize,
+    damp:   SampleType, //Damping lowpass memory.
+    out:    SampleType, //End of the half, feeds the other half.
+}
+
+impl Tank {
+    fn new(side: usize, scale: SampleType, excursion: usize) -> Self {
+        let len1 = scaled(TANK_DELAY1[side], scale);
+        let len2 = scaled(TANK_DELAY2[side], scale);
+        Tank {
+            ap1:    Diffuser::new(scaled(TANK_AP1[side], scale), excursion),
+            delay1: DelayLine::new(len1),
+            ap2:    Diffuser::new(scaled(TANK_AP2[side], scale), 0),
+            delay2: DelayLine::new(len2),
+            len1,
+            len2,
+            damp:   0.0,
+            out:    0.0,
+        }
+    }
+
+    #[inline]
+    fn tick(&mut self, 
+            smpl_in: SampleType, 
+            decay: SampleType, 
+            diff1: SampleType, 
+            diff2: SampleType, 
+            damping: SampleType, 
+            excursion: SampleType) 
+    {
+        let smpl = self.ap1.tick_mod(smpl_in, -diff1, excursion);
+
+        let d1 = self.delay1.tap(self.len1);
+        self.delay1.write(smpl);
+
+        self.damp = (d1 * (1.0 - damping)) + (self.damp * damping);
+        let smpl = self.ap2.tick(self.damp * decay, diff2);
+
+        let d2 = self.delay2.tap(self.len2);
+        self.delay2.write(smpl);
+
+        self.out = d2 * decay;
+        if !self.out.is_finite() {
+            self.out = 0.0;
+        }
+    }
+}
+
+#[inline]
+fn scaled(len: usize, scale: SampleType) -> usize {
+    let len = ((len as SampleType) * scale) as usize;
+    if len < 1 { 1 } else { len }
+}
+
+/***********************************************************************
+ * Plate
+ **********************************************************************/
+
+///
+///Dattorro plate reverb. Inputs on ports 0 and 1 are summed, outputs 
+///on ports 0 and 1 are decorrelated left and right.
+///
+///Defaults are fully wet for use as a send: connect any number of rack
+///effects to its inputs and its outputs to the rack outputs.
+///
+pub struct Plate {
+    predelay:  Smooth,     //Pre-delay in samples.
+    bandwidth: SampleType, //Input lowpass, 1 is open.
+    indiff1:   SampleType, //First input diffuser pair coefficient.
+    indiff2:   SampleType, //Second input diffuser pair coefficient.
+    decaydiff: SampleType, //Tank modulated diffuser coefficient.
+    decay:     Smooth,     //Tank gain.
+    damping:   SampleType, //Tank lowpass, 0 is open.
+    modrate:   SampleType, //Tank modulation rate in Hz.
+    moddepth:  SampleType, //Tank modulation depth 0..1.
+    wet:       Smooth,
+    dry:       Smooth,
+    rate:      SampleType, //Sample rate.
+
+    excursion: SampleType, //Modulation depth in samples at full depth.
+    pre:       DelayLine,
+    bw:        SampleType, //Bandwidth lowpass memory.
+    input:     [Diffuser; 4],
+    tanks:     [Tank; 2],
+    taps:      [[usize; 7]; 2], //Output taps at the sample rate.
+    lfo:       Phasor,
+}
+
+impl Default for Plate {
+    fn default() -> Self {
+        let mut plate = Plate {
+            predelay:  Smooth::new(0.0),
+            bandwidth: 0.9995,
+            indiff1:   0.75,
+            indiff2:   0.625,
+            decaydiff: 0.7,
+            decay:     Smooth::new(0.5),
+            damping:   0.0005,
+            modrate:   1.0,
+            moddepth:  0.5,
+            wet:       Smooth::new(1.0),
+            dry:       Smooth::new(0.0),
+            rate:      SAMPLE_RATE,
+            excursion: 0.0,
+            pre:       DelayLine::default(),
+            bw:        0.0,
+            input:     [Diffuser::new(1, 0), Diffuser::new(1, 0), Diffuser::new(1, 0), Diffuser::new(1, 0)],
+            tanks:     [Tank::new(0, 1.0, 0), Tank::new(1, 1.0, 0)],
+            taps:      TAPS,
+            lfo:       Phasor::default(),
+        };
+        plate.prepare(SAMPLE_RATE, 0);
+        plate.reset();
+        plate
+    }
+}
+
+impl Effect for Plate {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in, smpl_in).0
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in, *smpl_in).0;
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+    fn num_outputs(&mut self) -> usize { 2 }
+
+///
+///Left on port 0, right on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        match (inputs, outputs) {
+            ([in_l, in_r, ..], [out_l, out_r, ..]) => {
+                for (((smpl_l, smpl_r), l), r) in out_l.iter_mut()
+                                                       .zip(out_r.iter_mut())
+                                                       .zip(in_l.iter())
+                                                       .zip(in_r.iter())
+                {
+                    let (ol, or) = self.tick(*l, *r);
+                    *smpl_l = ol;
+                    *smpl_r = or;
+                }
+            },
+            ([input, ..], [output, ..]) => self.process_block(input, output),
+            _ => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.predelay.reset(0.0);
+        self.bandwidth = 0.9995;
+        self.indiff1   = 0.75;
+        self.indiff2   = 0.625;
+        self.decaydiff = 0.7;
+        self.decay.reset(0.5);
+        self.damping   = 0.0005;
+        self.modrate   = 1.0;
+        self.moddepth  = 0.5;
+        self.wet.reset(1.0);
+        self.dry.reset(0.0);
+        self.clear();
+    }
+
+///
+///Allocate the network scaled to the sample rate. Contents are 
+///cleared.
+///
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        let scale = sample_rate / TUNING_RATE;
+        let exc   = (MAX_EXCURSION * scale) as usize;
+        let pre   = self.predelay.target() / self.rate;
+
+        self.excursion = MAX_EXCURSION * scale;
+        self.pre   = DelayLine::new((PREDELAY_SECONDS * sample_rate) as usize);
+        self.input = [
+            Diffuser::new(scaled(INPUT_AP[0], scale), 0),
+            Diffuser::new(scaled(INPUT_AP[1], scale), 0),
+            Diffuser::new(scaled(INPUT_AP[2], scale), 0),
+            Diffuser::new(scaled(INPUT_AP[3], scale), 0),
+        ];
+        self.tanks = [Tank::new(0, scale, exc), Tank::new(1, scale, exc)];
+
+        for (taps, tuning) in self.taps.iter_mut().zip(TAPS.iter()) {
+            for (tap, len) in taps.iter_mut().zip(tuning.iter()) {
+                *tap = scaled(*len, scale);
+            }
+        }
+
+        self.rate = sample_rate;
+        self.lfo.sample_rate(sample_rate);
+        self.predelay.sample_rate(sample_rate);
+        self.decay.sample_rate(sample_rate);
+        self.wet.sample_rate(sample_rate);
+        self.dry.sample_rate(sample_rate);
+        self.predelay.reset(pre * sample_rate);
+        self.bw = 0.0;
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0  => { self.predelay(val); },
+            1  => { self.bandwidth = val; },
+            2  => { self.indiff1 = val; },
+            3  => { self.indiff2 = val; },
+            4  => { self.decaydiff = val; },
+            5  => { self.decay(val); },
+            6  => { self.damping = val; },
+            7  => { self.modrate = val; },
+            8  => { self.moddepth = val; },
+            9  => { self.wet(val); },
+            10 => { self.dry(val); },
+            _  => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0  => Ok(self.predelay.target() / self.rate),
+            1  => Ok(self.bandwidth),
+            2  => Ok(self.indiff1),
+            3  => Ok(self.indiff2),
+            4  => Ok(self.decaydiff),
+            5  => Ok(self.decay.target()),
+            6  => Ok(self.damping),
+            7  => Ok(self.modrate),
+            8  => Ok(self.moddepth),
+            9  => Ok(self.wet.target()),
+            10 => Ok(self.dry.target()),
+            _  => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Plate {
+    #[inline]
+    fn tick(&mut self, smpl_l: SampleType, smpl_r: SampleType) -> (SampleType, SampleType) {
+        let pre = self.predelay.next();
+        let mono = (smpl_l + smpl_r) * 0.5;
+        let smpl = if pre < 1.0 {
+            self.pre.write(mono);
+            self.pre.tap(1)
+        } else {
+            let smpl = self.pre.read(pre);
+            self.pre.write(mono);
+            smpl
+        };
+
+//Bandwidth then input diffusion.
+        self.bw = (smpl * self.bandwidth) + (self.bw * (1.0 - self.bandwidth));
+        let mut smpl = self.bw;
+        smpl = self.input[0].tick(smpl, self.indiff1);
+        smpl = self.input[1].tick(smpl, self.indiff1);
+        smpl = self.input[2].tick(smpl, self.indiff2);
+        smpl = self.input[3].tick(smpl, self.indiff2);
+
+//Tank. Each half is fed by the end of the other.
+        let decay = self.decay.next();
+        let diff2 = decay + 0.15;
+        let diff2 = diff2.clamp(0.25, 0.5);
+        let phase = self.lfo.next(self.modrate);
+        let exc   = self.excursion * self.moddepth;
+        let exc_l = exc * Shape::Sine.value(phase);
+        let exc_r = exc * Shape::Sine.value(wrap(phase + 0.25));
+        let (end_l, end_r) = (self.tanks[0].out, self.tanks[1].out);
+
+        self.tanks[0].tick(smpl + end_r, decay, self.decaydiff, diff2, self.damping, exc_l);
+        self.tanks[1].tick(smpl + end_l, decay, self.decaydiff, diff2, self.damping, exc_r);
+
+        let wet = self.wet.next() * OUTPUT_GAIN;
+        let dry = self.dry.next();
+
+        (
+            (self.output(0) * wet) + (smpl_l * dry),
+            (self.output(1) * wet) + (smpl_r * dry)
+        )
+    }
+
+///
+///Sum the output taps for a side.
+///
+    #[inline]
+    fn output(&self, side: usize) -> SampleType {
+        let taps  = &self.taps[side];
+        let other = &self.tanks[1 - side];
+        let same  = &self.tanks[side];
+
+        other.delay1.tap(taps[0])
+            + other.delay1.tap(taps[1])
+            - other.ap2.line.tap(taps[2])
+            + other.delay2.tap(taps[3])
+            - same.delay1.tap(taps[4])
+            - same.ap2.line.tap(taps[5])
+            - same.delay2.tap(taps[6])
+    }
+
+    fn clear(&mut self) {
+        self.pre.clear();
+        for diffuser in self.input.iter_mut() {
+            diffuser.line.clear();
+        }
+        for tank in self.tanks.iter_mut() {
+            tank.ap1.line.clear();
+            tank.delay1.clear();
+            tank.ap2.line.clear();
+            tank.delay2.clear();
+            tank.damp = 0.0;
+            tank.out  = 0.0;
+        }
+        self.bw = 0.0;
+        self.lfo.reset(0.0);
+    }
+
+    pub fn predelay(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        let max = new.pre.max() as SampleType;
+        let delay = sec * new.rate;
+        new.predelay.set(if delay > max { max } else { delay });
+        new
+    }
+
+///
+///Tank gain 0..0.99. Sets the decay time.
+///
+    pub fn decay(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.decay.set(val);
+        new
+    }
+
+    pub fn wet(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.wet.set(val);
+        new
+    }
+
+    pub fn dry(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.dry.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plate::Plate;
+    use crate::Effect;
+
+    #[test]
+    fn stereo_decay() {
+        let mut plate = Plate::default();
+        plate.decay(0.7);
+        let mut imp = [0.0; 256];
+        imp[0] = 1.0;
+        let silence = [0.0; 256];
+        let (mut l, mut r) = ([0.0; 256], [0.0; 256]);
+
+        let mut diff  = 0.0;
+        let mut early = 0.0;
+        let mut late  = 0.0;
+        for blk in 0..2000 {
+            let input: &[f32] = if blk == 0 { &imp } else { &silence };
+            plate.process_ports(&[input, input], &mut [&mut l, &mut r]);
+            for (a, b) in l.iter().zip(r.iter()) {
+                assert!(a.is_finite() && b.is_finite());
+                diff += (a - b).abs();
+                if blk < 200 { early += a.abs(); } else if blk > 1800 { late += a.abs(); }
+            }
+        }
+        assert!(diff > 0.1);
+        assert!(early > 0.1);
+        assert!(late < early * 0.001);
+    }
+}
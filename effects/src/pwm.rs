@@ -22,105 +22,143 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use super::{SAMPLE_RATE_USIZE, SAMPLE_RATE, SampleType};
+use super::SampleType;
 use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::osc::{Oscillator, Wave};
+use crate::smooth::Smooth;
+
+static PARAMS: [Desc; 5] = [
+    Desc { name: "freq",   min: 0.01,     max: 20000.0, default: 440.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "scale",  min: -20000.0, max: 20000.0, default: 1.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "offset", min: -20000.0, max: 20000.0, default: 0.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "duty",   min: 0.0,      max: 1.0,     default: 0.5,   unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "mix",    min: 0.0,      max: 1.0,     default: 1.0,   unit: Unit::Ratio, curve: Curve::Linear },
+];
 
-///
-/// Return fractional part of sample.
-///
-#[inline]
-fn fract(val: SampleType) -> SampleType {
-    val - ((val as i32) as SampleType)
-}
-
-
-#[derive(Default)]
 pub struct Pwm {
-    pub freq:   SampleType,
-    pub scale:  SampleType,
-    pub offset: SampleType,
-    pub duty:   SampleType,
-    pub mix:    SampleType,
+    freq:   Smooth,     //Frequency in Hz.
+    scale:  Smooth,     //Output is scaled by this amount.
+    offset: Smooth,     //Then offset by this amount.
+    duty:   Smooth,     //Fraction of cycle output is high.
+    mix:    SampleType,
 
-    cnt:        usize,
+    osc:    Oscillator, //Band limited pulse.
+}
+
+impl Default for Pwm {
+    fn default() -> Self {
+        let mut osc = Oscillator::default();
+        osc.wave(Wave::Pulse);
+
+        Pwm {
+            freq:   Smooth::default(),
+            scale:  Smooth::default(),
+            offset: Smooth::default(),
+            duty:   Smooth::default(),
+            mix:    SampleType::default(),
+            osc,
+        }
+    }
 }
 
 impl Effect for Pwm {
     fn process(&mut self, _smpl_in: SampleType) -> SampleType {
-        self.cnt += 1;
-        if self.cnt > SAMPLE_RATE_USIZE {
-            self.cnt = 1;
-        }
-        
-        if fract((self.cnt as SampleType) / (SAMPLE_RATE / self.freq)) > self.duty {
-            (-1.0 * self.scale + self.offset)
-        } else {
-            (1.0 * self.scale + self.offset)
+        self.tick()
+    }
+
+///
+///Process block.
+///
+    fn process_block(&mut self, _input: &[SampleType], output: &mut [SampleType]) {
+        for smpl_out in output.iter_mut() {
+            *smpl_out = self.tick();
         }
     }
     
     fn reset(&mut self) {
-        self.cnt    = 1;
-        self.freq   = 440.0;
-        self.scale  = 1.0;
-        self.offset = 0.0;
-        self.duty   = 0.50;
-        self.mix    = 1.0;
+        self.osc.reset(0.0);
+        self.freq.reset(440.0);
+        self.scale.reset(1.0);
+        self.offset.reset(0.0);
+        self.duty.reset(0.50);
+        self.mix   = 1.0;
     }
 
-    fn num_params(&mut self) -> usize { 5 }
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.osc.sample_rate(sample_rate);
+        self.freq.sample_rate(sample_rate);
+        self.scale.sample_rate(sample_rate);
+        self.offset.sample_rate(sample_rate);
+        self.duty.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
 
-    fn set_param(&mut self, idx: usize, val: SampleType) {
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
         match idx {
             0 => { self.freq(val); },
             1 => { self.scale(val); },
             2 => { self.offset(val); },
             3 => { self.duty(val); },
             4 => { self.mix(val); },
-            _ => { panic!("Parameter doesn't exist.") }
+            _ => { return Err(ERROR::INDEX) }
         }
+        Ok(())
     }
 
-    fn get_param(&mut self, idx: usize) -> SampleType { 
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> { 
         match idx {
-            0 => self.freq,
-            1 => self.scale,
-            2 => self.offset,
-            3 => self.duty,
-            4 => self.mix,
-            _ => { panic!("Parameter doesn't exist.") }
+            0 => Ok(self.freq.target()),
+            1 => Ok(self.scale.target()),
+            2 => Ok(self.offset.target()),
+            3 => Ok(self.duty.target()),
+            4 => Ok(self.mix),
+            _ => Err(ERROR::INDEX)
         }
-    }  
-
+    }
 }
 
 impl Pwm {    
+///
+///Output current phase then advance. Phase accumulates so frequency
+///changes are continuous.
+///
+    #[inline]
+    fn tick(&mut self) -> SampleType {
+        let duty = self.duty.next();
+        self.osc.width(duty);
+        self.osc.next(self.freq.next()) * self.scale.next() + self.offset.next()
+    }
+
     pub fn freq(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.freq = val;
+        let new = self;
+        new.freq.set(val);
         new
     }
 
     pub fn scale(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.scale = val;
+        let new = self;
+        new.scale.set(val);
         new
     }
 
     pub fn offset(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.offset = val;
+        let new = self;
+        new.offset.set(val);
         new
     }
 
     pub fn duty(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.duty = val;
+        let new = self;
+        new.duty.set(val);
         new
     }
 
     pub fn mix(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
+        let new = self;
         new.mix = val;
         new
     }
@@ -0,0 +1,402 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::delayline::DelayLine;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use alloc::vec;
+use alloc::vec::Vec;
+
+///
+///Freeverb tunings are in samples at 44.1kHz and scaled to the sample
+///rate. The right channel is spread a little longer than the left.
+///
+const TUNING_RATE:    SampleType = 44100.0;
+const COMB_TUNING:    [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD:  usize = 23;
+
+const FIXED_GAIN:    SampleType = 0.015;
+const SCALE_ROOM:    SampleType = 0.28;
+const OFFSET_ROOM:   SampleType = 0.7;
+const SCALE_DAMP:    SampleType = 0.4;
+const ALLPASS_FB:    SampleType = 0.5;
+
+const PREDELAY_SECONDS: SampleType = 0.5;
+
+static PARAMS: [Desc; 6] = [
+    Desc { name: "room",     min: 0.0, max: 1.0,              default: 0.5, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "damping",  min: 0.0, max: 1.0,              default: 0.5, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "width",    min: 0.0, max: 1.0,              default: 1.0, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "predelay", min: 0.0, max: PREDELAY_SECONDS, default: 0.0, unit: Unit::Seconds, curve: Curve::Linear },
+    Desc { name: "wet",      min: 0.0, max: 1.0,              default: 0.3, unit: Unit::Ratio,   curve: Curve::Linear },
+    Desc { name: "dry",      min: 0.0, max: 1.0,              default: 0.7, unit: Unit::Ratio,   curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Comb
+ **********************************************************************/
+
+///
+///Lowpass feedback comb.
+///
+struct Comb {
+    buf:   Vec<SampleType>,
+    idx:   usize,
+    store: SampleType, //Lowpass memory.
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Comb { buf: vec![0.0; len], idx: 0, store: 0.0 }
+    }
+
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType, feedback: SampleType, damp: SampleType) -> SampleType {
+        let out = self.buf[self.idx];
+        self.store = (out * (1.0 - damp)) + (self.store * damp);
+        self.buf[self.idx] = smpl_in + (self.store * feedback);
+        self.idx = (self.idx + 1) % self.buf.len();
+        out
+    }
+
+    fn clear(&mut self) {
+        for smpl in self.buf.iter_mut() { *smpl = 0.0; }
+        self.store = 0.0;
+    }
+}
+
+/***********************************************************************
+ * Allpass
+ **********************************************************************/
+
+///
+///Schroeder allpass.
+///
+struct Allpass {
+    buf: Vec<SampleType>,
+    idx: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Allpass { buf: vec![0.0; len], idx: 0 }
+    }
+
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> SampleType {
+        let buf = self.buf[self.idx];
+        self.buf[self.idx] = smpl_in + (buf * ALLPASS_FB);
+        self.idx = (self.idx + 1) % self.buf.len();
+        buf - smpl_in
+    }
+
+    fn clear(&mut self) {
+        for smpl in self.buf.iter_mut() { *smpl = 0.0; }
+    }
+}
+
+/***********************************************************************
+ * Reverb
+ **********************************************************************/
+
+///
+///Freeverb. Eight parallel combs into four series allpasses per 
+///channel. Stereo on ports 0 and 1. Both inputs are summed into the
+///networks; width sets how much each network feeds the opposite side.
+///
+///Buffers are allocated from the heap when the sample rate is set. At
+///48kHz the networks take about 100KB plus 128KB for pre-delay.
+///
+pub struct Reverb {
+    room:     SampleType, //Room size 0..1.
+    damping:  SampleType, //High frequency damping 0..1.
+    width:    Smooth,     //Stereo width 0..1.
+    predelay: Smooth,     //Pre-delay in samples.
+    wet:      Smooth,
+    dry:      Smooth,
+    rate:     SampleType, //Sample rate.
+
+    feedback: SampleType, //Comb feedback from room.
+    damp:     SampleType, //Comb damping from damping.
+    pre:      DelayLine,
+    comb_l:   Vec<Comb>,
+    comb_r:   Vec<Comb>,
+    ap_l:     Vec<Allpass>,
+    ap_r:     Vec<Allpass>,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        let mut reverb = Reverb {
+            room:     0.5,
+            damping:  0.5,
+            width:    Smooth::new(1.0),
+            predelay: Smooth::new(0.0),
+            wet:      Smooth::new(0.3),
+            dry:      Smooth::new(0.7),
+            rate:     SAMPLE_RATE,
+            feedback: 0.0,
+            damp:     0.0,
+            pre:      DelayLine::default(),
+            comb_l:   Vec::new(),
+            comb_r:   Vec::new(),
+            ap_l:     Vec::new(),
+            ap_r:     Vec::new(),
+        };
+        reverb.prepare(SAMPLE_RATE, 0);
+        reverb.reset();
+        reverb
+    }
+}
+
+impl Effect for Reverb {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in, smpl_in).0
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in, *smpl_in).0;
+        }
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+    fn num_outputs(&mut self) -> usize { 2 }
+
+///
+///Left on port 0, right on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        match (inputs, outputs) {
+            ([in_l, in_r, ..], [out_l, out_r, ..]) => {
+                for (((smpl_l, smpl_r), l), r) in out_l.iter_mut()
+                                                       .zip(out_r.iter_mut())
+                                                       .zip(in_l.iter())
+                                                       .zip(in_r.iter())
+                {
+                    let (ol, or) = self.tick(*l, *r);
+                    *smpl_l = ol;
+                    *smpl_r = or;
+                }
+            },
+            ([input, ..], [output, ..]) => self.process_block(input, output),
+            _ => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.room(0.5);
+        self.damping(0.5);
+        self.width.reset(1.0);
+        self.predelay.reset(0.0);
+        self.wet.reset(0.3);
+        self.dry.reset(0.7);
+        self.clear();
+    }
+
+///
+///Allocate networks scaled to the sample rate. Contents are cleared.
+///
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        let scale  = sample_rate / TUNING_RATE;
+        let len    = |tuning: usize| -> usize { 
+            let len = ((tuning as SampleType) * scale) as usize;
+            if len < 1 { 1 } else { len }
+        };
+        let pre    = self.predelay.target() / self.rate;
+
+        self.comb_l = COMB_TUNING.iter().map(|t| Comb::new(len(*t))).collect();
+        self.comb_r = COMB_TUNING.iter().map(|t| Comb::new(len(*t + STEREO_SPREAD))).collect();
+        self.ap_l   = ALLPASS_TUNING.iter().map(|t| Allpass::new(len(*t))).collect();
+        self.ap_r   = ALLPASS_TUNING.iter().map(|t| Allpass::new(len(*t + STEREO_SPREAD))).collect();
+        self.pre    = DelayLine::new((PREDELAY_SECONDS * sample_rate) as usize);
+
+        self.rate = sample_rate;
+        self.width.sample_rate(sample_rate);
+        self.predelay.sample_rate(sample_rate);
+        self.wet.sample_rate(sample_rate);
+        self.dry.sample_rate(sample_rate);
+        self.predelay.reset(pre * sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.room(val); },
+            1 => { self.damping(val); },
+            2 => { self.width(val); },
+            3 => { self.predelay(val); },
+            4 => { self.wet(val); },
+            5 => { self.dry(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.room),
+            1 => Ok(self.damping),
+            2 => Ok(self.width.target()),
+            3 => Ok(self.predelay.target() / self.rate),
+            4 => Ok(self.wet.target()),
+            5 => Ok(self.dry.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Reverb {
+    #[inline]
+    fn tick(&mut self, smpl_l: SampleType, smpl_r: SampleType) -> (SampleType, SampleType) {
+        let pre = self.predelay.next();
+        let smpl_in = if pre < 1.0 {
+            self.pre.write((smpl_l + smpl_r) * FIXED_GAIN);
+            self.pre.tap(1)
+        } else {
+            let smpl = self.pre.read(pre);
+            self.pre.write((smpl_l + smpl_r) * FIXED_GAIN);
+            smpl
+        };
+
+        let (mut acc_l, mut acc_r) = (0.0, 0.0);
+        for comb in self.comb_l.iter_mut() {
+            acc_l += comb.tick(smpl_in, self.feedback, self.damp);
+        }
+        for comb in self.comb_r.iter_mut() {
+            acc_r += comb.tick(smpl_in, self.feedback, self.damp);
+        }
+        for ap in self.ap_l.iter_mut() {
+            acc_l = ap.tick(acc_l);
+        }
+        for ap in self.ap_r.iter_mut() {
+            acc_r = ap.tick(acc_r);
+        }
+
+        let width = self.width.next();
+        let wet   = self.wet.next();
+        let dry   = self.dry.next();
+        let wet1  = wet * ((width / 2.0) + 0.5);
+        let wet2  = wet * ((1.0 - width) / 2.0);
+
+        (
+            (acc_l * wet1) + (acc_r * wet2) + (smpl_l * dry),
+            (acc_r * wet1) + (acc_l * wet2) + (smpl_r * dry)
+        )
+    }
+
+    fn clear(&mut self) {
+        self.pre.clear();
+        for comb in self.comb_l.iter_mut().chain(self.comb_r.iter_mut()) {
+            comb.clear();
+        }
+        for ap in self.ap_l.iter_mut().chain(self.ap_r.iter_mut()) {
+            ap.clear();
+        }
+    }
+
+///
+///Room size 0..1. Larger rooms decay longer.
+///
+    pub fn room(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.room = val;
+        new.feedback = (val * SCALE_ROOM) + OFFSET_ROOM;
+        new
+    }
+
+///
+///High frequency damping 0..1.
+///
+    pub fn damping(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.damping = val;
+        new.damp = val * SCALE_DAMP;
+        new
+    }
+
+    pub fn width(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.width.set(val);
+        new
+    }
+
+///
+///Pre-delay in seconds.
+///
+    pub fn predelay(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        let max = new.pre.max() as SampleType;
+        let delay = sec * new.rate;
+        new.predelay.set(if delay > max { max } else { delay });
+        new
+    }
+
+    pub fn wet(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.wet.set(val);
+        new
+    }
+
+    pub fn dry(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.dry.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reverb::Reverb;
+    use crate::Effect;
+
+    #[test]
+    fn decay() {
+//Impulse rings out then decays. Sizes follow the sample rate.
+        let mut rv = Reverb::default();
+        rv.prepare(96000.0, 32);
+        rv.dry(0.0).wet(1.0).predelay(0.1);
+        rv.process(1.0);
+
+        let mut early = 0.0;
+        let mut late  = 0.0;
+        for i in 1..(96000 * 8) {
+            let out = rv.process(0.0).abs();
+            assert!(out.is_finite());
+            if i < 9600 { assert!(out == 0.0); }
+            if i < 96000 { early += out; } else if i > (96000 * 7) { late += out; }
+        }
+        assert!(early > 0.01);
+        assert!(late < early * 0.001);
+    }
+}
@@ -0,0 +1,194 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, SHAPE_MAX};
+
+static PARAMS: [Desc; 4] = [
+    Desc { name: "freq",   min: 0.1, max: 5000.0,    default: 440.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "shape",  min: 0.0, max: SHAPE_MAX, default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "source", min: 0.0, max: 1.0,       default: 0.0,   unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "mix",    min: 0.0, max: 1.0,       default: 1.0,   unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * RingMod
+ **********************************************************************/
+
+///
+///Ring modulator. Multiplies the input (port 0) by a carrier. With 
+///source 0 the carrier is the internal oscillator, with source 1 it is
+///whatever is connected to port 1, e.g. a Sine or another audio signal.
+///
+pub struct RingMod {
+    freq:     SampleType, //Internal carrier frequency in Hz.
+    shape:    Shape,      //Internal carrier waveform.
+    external: bool,       //Carrier from port 1.
+    mix:      Smooth,     //0 dry .. 1 modulated.
+    osc:      Phasor,
+}
+
+impl Default for RingMod {
+    fn default() -> Self {
+        let mut rm = RingMod {
+            freq:     440.0,
+            shape:    Shape::Sine,
+            external: false,
+            mix:      Smooth::new(1.0),
+            osc:      Phasor::default(),
+        };
+        rm.prepare(SAMPLE_RATE, 0);
+        rm
+    }
+}
+
+impl Effect for RingMod {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        let carrier = self.shape.value(self.osc.next(self.freq));
+        self.tick(smpl_in, carrier)
+    }
+
+    fn num_inputs(&mut self) -> usize { 2 }
+
+///
+///Audio on port 0, external carrier on port 1.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let output = match outputs.first_mut() {
+            Some(output) => output,
+            None => return,
+        };
+
+        match inputs {
+            [audio, carrier, ..] if self.external => {
+                for ((smpl_out, smpl_in), smpl_car) in output.iter_mut()
+                                                             .zip(audio.iter())
+                                                             .zip(carrier.iter())
+                {
+                    *smpl_out = self.tick(*smpl_in, *smpl_car);
+                }
+            },
+            [audio, ..] => self.process_block(audio, output),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq = 440.0;
+        self.shape = Shape::Sine;
+        self.external = false;
+        self.mix.reset(1.0);
+        self.osc.reset(0.0);
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.osc.sample_rate(sample_rate);
+        self.mix.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.freq(val); },
+            1 => { self.shape(Shape::from_param(val)); },
+            2 => { self.external(val >= 0.5); },
+            3 => { self.mix(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.freq),
+            1 => Ok(self.shape.to_param()),
+            2 => Ok(if self.external { 1.0 } else { 0.0 }),
+            3 => Ok(self.mix.target()),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl RingMod {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType, carrier: SampleType) -> SampleType {
+        let mix = self.mix.next();
+        smpl_in * ((1.0 - mix) + (carrier * mix))
+    }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq = hz;
+        new
+    }
+
+    pub fn shape(&mut self, shape: Shape) -> &mut Self {
+        let new = self;
+        new.shape = shape;
+        new
+    }
+
+///
+///Take the carrier from port 1 instead of the internal oscillator.
+///
+    pub fn external(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.external = on;
+        new
+    }
+
+    pub fn mix(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.mix.set(val);
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ringmod::RingMod;
+    use crate::Effect;
+
+    #[test]
+    fn carrier_port() {
+        let mut rm = RingMod::default();
+        rm.external(true);
+        let audio   = [0.5; 64];
+        let carrier: Vec<f32> = (0..64).map(|i| if i % 2 == 0 { 1.0 } else { -1.0 }).collect();
+        let mut out = [0.0; 64];
+        rm.process_ports(&[&audio, &carrier], &mut [&mut out]);
+        for (y, c) in out.iter().zip(carrier.iter()) {
+            assert!((y - (0.5 * c)).abs() < 1e-6);
+        }
+    }
+}
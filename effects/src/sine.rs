@@ -22,14 +22,24 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
-use super::{SAMPLE_RATE_USIZE, SAMPLE_RATE, SampleType};
+use super::SampleType;
 use crate::Effect;
-
-const PI:                 SampleType = 3.14159265358979;
-const QUARTER_TAU:        SampleType = PI / 2.0;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::osc::Oscillator;
+use crate::smooth::Smooth;
+use core::f32::consts::{FRAC_PI_2, PI, TAU};
+
+static PARAMS: [Desc; 4] = [
+    Desc { name: "freq",   min: 0.01,     max: 20000.0, default: 440.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "scale",  min: -20000.0, max: 20000.0, default: 1.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "offset", min: -20000.0, max: 20000.0, default: 0.0,   unit: Unit::None,  curve: Curve::Linear },
+    Desc { name: "mix",    min: 0.0,      max: 1.0,     default: 0.0,   unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+const QUARTER_TAU:        SampleType = FRAC_PI_2;
 const HALF_TAU:           SampleType = PI;
 const THREE_QUARTERS_TAU: SampleType = HALF_TAU + QUARTER_TAU;
-const TAU:                SampleType = PI * 2.0;
 
 #[inline]
 fn taylor9(x: SampleType) -> SampleType {
@@ -43,10 +53,10 @@ fn taylor9(x: SampleType) -> SampleType {
 
 ///
 ///Given values 0..TAU inclusive return an approximation of sin() using
-///taylor series.
+///taylor series. Fills the oscillator sine table.
 ///
 #[inline]
-fn taylor(rad: SampleType) -> SampleType {
+pub(crate) fn taylor(rad: SampleType) -> SampleType {
     if rad <= HALF_TAU {
         if rad <= QUARTER_TAU {
             taylor9(rad)
@@ -62,88 +72,101 @@ fn taylor(rad: SampleType) -> SampleType {
     }
 }
 
-///
-/// Return fractional part of sample.
-///
-#[inline]
-fn fract(val: SampleType) -> SampleType {
-    val - ((val as i32) as SampleType)
-}
-
 #[derive(Default)]
 pub struct Sine {
-    pub freq:   SampleType,
-    pub scale:  SampleType,
-    pub offset: SampleType,
-    pub mix:    SampleType,
+    freq:   Smooth,     //Frequency in Hz.
+    scale:  Smooth,     //Output is scaled by this amount.
+    offset: Smooth,     //Then offset by this amount.
+    mix:    SampleType,
 
-    cnt:        usize,
+    osc:    Oscillator, //Table lookup sine.
 }
 
+
 impl Effect for Sine {
     fn process(&mut self, _smpl_in: SampleType) -> SampleType {
-        self.cnt += 1;
-        if self.cnt == SAMPLE_RATE_USIZE {
-            self.cnt = 0;
-        }
+        self.tick()
+    }
 
-        taylor (
-            TAU * fract((self.cnt as SampleType) / (SAMPLE_RATE / self.freq))
-        ) * self.scale + self.offset
+///
+///Process block.
+///
+    fn process_block(&mut self, _input: &[SampleType], output: &mut [SampleType]) {
+        for smpl_out in output.iter_mut() {
+            *smpl_out = self.tick();
+        }
     }
 
     fn reset(&mut self) {
-        self.freq   = 440.0;
-        self.scale  = 1.0;
-        self.offset = 0.0;
-        self.mix    = 0.0;
-        self.cnt    = 0;
+        self.freq.reset(440.0);
+        self.scale.reset(1.0);
+        self.offset.reset(0.0);
+        self.mix = 0.0;
+        self.osc.reset(0.0);
     }
     
-    fn num_params(&mut self) -> usize { 4 }
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.osc.sample_rate(sample_rate);
+        self.freq.sample_rate(sample_rate);
+        self.scale.sample_rate(sample_rate);
+        self.offset.sample_rate(sample_rate);
+    }
 
-    fn set_param(&mut self, idx: usize, val: SampleType) {
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
         match idx {
             0 => { self.freq(val); },
             1 => { self.scale(val); },
             2 => { self.offset(val); },
             3 => { self.mix(val); },
-            _ => { panic!("Parameter doesn't exist.") }
+            _ => { return Err(ERROR::INDEX) }
         }
+        Ok(())
     }
 
-    fn get_param(&mut self, idx: usize) -> SampleType { 
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> { 
         match idx {
-            0 => self.freq,
-            1 => self.scale,
-            2 => self.offset,
-            3 => self.mix,
-            _ => { panic!("Parameter doesn't exist.") }
+            0 => Ok(self.freq.target()),
+            1 => Ok(self.scale.target()),
+            2 => Ok(self.offset.target()),
+            3 => Ok(self.mix),
+            _ => Err(ERROR::INDEX)
         }
     }
 }
 
 impl Sine {
+///
+///Output current phase then advance. Phase accumulates so frequency
+///changes are continuous.
+///
+    #[inline]
+    fn tick(&mut self) -> SampleType {
+        self.osc.next(self.freq.next()) * self.scale.next() + self.offset.next()
+    }
+
     pub fn freq(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.freq = val;
+        let new = self;
+        new.freq.set(val);
         new
     }
 
     pub fn scale(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.scale = val;
+        let new = self;
+        new.scale.set(val);
         new
     }
 
     pub fn offset(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
-        new.offset = val;
+        let new = self;
+        new.offset.set(val);
         new
     }
 
     pub fn mix(&mut self, val: SampleType) -> &mut Self {
-        let mut new = self;
+        let new = self;
         new.mix = val;
         new
     }
@@ -151,23 +174,22 @@ impl Sine {
 
 #[cfg(test)]
 mod tests {
-    use crate::sine::{Sine, TAU, HALF_TAU, taylor, taylor9, fract};
-    use super::{SAMPLE_RATE_USIZE, SAMPLE_RATE, SampleType};
+    use crate::sine::{TAU, HALF_TAU, taylor};
+    use crate::wave::wrap;
+    use crate::{SAMPLE_RATE_USIZE, SAMPLE_RATE, SampleType};
 
     #[test]
     fn sine() {
         for i in 0..360 {
             let rad = (i as SampleType) * (HALF_TAU / 180.0);
-            let t = taylor(rad);
-            let t4 = taylor9(rad);
+            let t   = taylor(rad);
             let s   = SampleType::sin(rad);
             let dif = t - s;
-            println!("rad = {}, t = {}, t4 = {}, s = {}", rad, t, t4, s);
-//            assert!(SampleType::abs(dif) < 0.000004);
+            assert!(SampleType::abs(dif) < 0.000004);
         }
 
         for cnt in 0..SAMPLE_RATE_USIZE {
-            let rad = TAU * fract((cnt as SampleType) / (SAMPLE_RATE / 440.0));
+            let rad = TAU * wrap((cnt as SampleType) / (SAMPLE_RATE / 440.0));
             let t4  = taylor(rad); 
             let s   = SampleType::sin(rad);
             let dif = t4 - s;
@@ -0,0 +1,283 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use libm::{expf, fabsf};
+
+///
+///Default ramp time in seconds.
+///
+pub const SMOOTH_SECONDS: SampleType = 0.01;
+
+///
+///Differences smaller than this, relative to the target (or absolute 
+///for targets below 1), are considered settled.
+///
+const SETTLED: SampleType = 0.000001;
+
+/***********************************************************************
+ * Ramp
+ **********************************************************************/
+
+///
+///Shape of the path taken from the current value to the target.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ramp {
+    Linear,  //Straight line. Reaches target in exactly "time" seconds.
+    OnePole, //Exponential approach. "time" is the time constant.
+}
+
+/***********************************************************************
+ * Smooth
+ **********************************************************************/
+
+///
+///Smoothed parameter. Setting a new target starts a ramp from the 
+///current value instead of jumping. Call next() once per sample to 
+///advance the ramp.
+///
+#[derive(Clone, Copy)]
+pub struct Smooth {
+    cur:  SampleType, //Current value.
+    tgt:  SampleType, //Target value.
+    step: SampleType, //Linear increment per sample.
+    cnt:  usize,      //Linear samples remaining.
+    coef: SampleType, //One pole coefficient.
+    len:  usize,      //Ramp time in samples.
+    time: SampleType, //Ramp time in seconds.
+    ramp: Ramp,       //Ramp shape.
+    rate: SampleType, //Sample rate.
+}
+
+impl Default for Smooth {
+    fn default() -> Self {
+        let mut smooth = Smooth {
+            cur:  0.0,
+            tgt:  0.0,
+            step: 0.0,
+            cnt:  0,
+            coef: 0.0,
+            len:  0,
+            time: SMOOTH_SECONDS,
+            ramp: Ramp::OnePole,
+            rate: SAMPLE_RATE,
+        };
+        smooth.update();
+        smooth
+    }
+}
+
+impl Smooth {
+///
+///New smoother settled at val.
+///
+    pub fn new(val: SampleType) -> Self {
+        let mut smooth = Smooth::default();
+        smooth.reset(val);
+        smooth
+    }
+
+///
+///Recalculate coefficients from the ramp time.
+///
+    fn update(&mut self) {
+        let len = self.time * self.rate;
+        self.len = (len + 0.5) as usize;
+        self.coef = if len > 0.0 { expf(-1.0 / len) } else { 0.0 };
+    }
+
+///
+///Set ramp shape.
+///
+    pub fn ramp(&mut self, ramp: Ramp) -> &mut Self {
+        let new = self;
+        new.ramp = ramp;
+        new
+    }
+
+///
+///Set ramp time in seconds. Zero disables smoothing.
+///
+    pub fn time(&mut self, sec: SampleType) -> &mut Self {
+        let new = self;
+        new.time = if sec > 0.0 { sec } else { 0.0 };
+        new.update();
+        new
+    }
+
+///
+///Set sample rate ramp time is measured against.
+///
+    pub fn sample_rate(&mut self, rate: SampleType) -> &mut Self {
+        let new = self;
+        new.rate = rate;
+        new.update();
+        new
+    }
+
+///
+///Set a new target and start ramping towards it.
+///
+    pub fn set(&mut self, tgt: SampleType) {
+        if tgt != self.tgt {
+            self.tgt = tgt;
+            if self.len > 0 {
+                self.cnt  = self.len;
+                self.step = (self.tgt - self.cur) / (self.len as SampleType);
+            } else {
+                self.cnt = 0;
+                self.cur = tgt;
+            }
+        }
+    }
+
+///
+///Jump to val immediately.
+///
+    pub fn reset(&mut self, val: SampleType) {
+        self.cur = val;
+        self.tgt = val;
+        self.cnt = 0;
+    }
+
+///
+///Value being ramped to.
+///
+    #[inline]
+    pub fn target(&self) -> SampleType {
+        self.tgt
+    }
+
+///
+///Current value without advancing.
+///
+    #[inline]
+    pub fn value(&self) -> SampleType {
+        self.cur
+    }
+
+///
+///True when the current value has reached the target.
+///
+    #[inline]
+    pub fn settled(&self) -> bool {
+        self.cur == self.tgt
+    }
+
+///
+///Advance one sample and return the current value.
+///
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> SampleType {
+        if self.cur != self.tgt {
+            match self.ramp {
+                Ramp::Linear => {
+                    if self.cnt > 1 {
+                        self.cnt -= 1;
+                        self.cur += self.step;
+                    } else {
+                        self.cnt = 0;
+                        self.cur = self.tgt;
+                    }
+                },
+                Ramp::OnePole => {
+//Snap once close, or once the step is too small to change cur in f32.
+                    let prev = self.cur;
+                    let scale = fabsf(self.tgt);
+                    let scale = if scale > 1.0 { scale } else { 1.0 };
+                    self.cur = self.tgt + ((self.cur - self.tgt) * self.coef);
+                    if self.cur == prev || fabsf(self.cur - self.tgt) < SETTLED * scale {
+                        self.cur = self.tgt;
+                    }
+                }
+            }
+        }
+        self.cur
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::smooth::{Smooth, Ramp};
+    use super::{SAMPLE_RATE, SampleType};
+
+    #[test]
+    fn linear() {
+        let mut smooth = Smooth::new(0.0);
+        smooth.ramp(Ramp::Linear).time(0.001);
+        smooth.set(1.0);
+
+        let len = (0.001 * SAMPLE_RATE + 0.5) as usize;
+        let mut prev = 0.0;
+        for _ in 0..(len - 1) {
+            let val = smooth.next();
+            assert!(val > prev && val < 1.0);
+            prev = val;
+        }
+        assert!(smooth.next() == 1.0);
+        assert!(smooth.settled());
+    }
+
+    #[test]
+    fn one_pole() {
+        let mut smooth = Smooth::new(1.0);
+        smooth.ramp(Ramp::OnePole).time(0.001);
+        smooth.set(0.0);
+
+//One time constant gets ~63% of the way there.
+        let len = (0.001 * SAMPLE_RATE + 0.5) as usize;
+        for _ in 0..len {
+            smooth.next();
+        }
+        assert!((smooth.value() - 0.3679).abs() < 0.01);
+
+        for _ in 0..(len * 20) {
+            smooth.next();
+        }
+        assert!(smooth.settled());
+        assert!(smooth.value() == 0.0 as SampleType);
+    }
+
+    #[test]
+    fn large() {
+//Large targets settle even though f32 can't get within SETTLED of them.
+        let mut smooth = Smooth::new(1000.0);
+        smooth.set(1500.0);
+        for _ in 0..(SAMPLE_RATE as usize) {
+            smooth.next();
+        }
+        assert!(smooth.settled());
+        assert!(smooth.value() == 1500.0);
+    }
+
+    #[test]
+    fn immediate() {
+        let mut smooth = Smooth::new(0.0);
+        smooth.time(0.0);
+        smooth.set(0.5);
+        assert!(smooth.value() == 0.5);
+    }
+}
@@ -0,0 +1,318 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use libm::{tanf, tanhf};
+use core::f32::consts::PI;
+
+///
+///Band pass integrator level where soft limiting sets in. Keeps a self
+///oscillating filter at a steady level.
+///
+const LIMIT: SampleType = 4.0;
+
+static PARAMS: [Desc; 2] = [
+    Desc { name: "freq", min: 20.0, max: 20000.0, default: 1000.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "res",  min: 0.0,  max: 1.1,     default: 0.0,    unit: Unit::Ratio, curve: Curve::Linear },
+];
+
+/***********************************************************************
+ * Filter
+ **********************************************************************/
+
+///
+///Simultaneous state variable filter outputs.
+///
+#[derive(Clone, Copy, Default)]
+pub struct Outputs {
+    pub lp:    SampleType,
+    pub bp:    SampleType,
+    pub hp:    SampleType,
+    pub notch: SampleType,
+}
+
+///
+///Topology preserving transform (Zavalishin) state variable filter.
+///Trapezoidal integration keeps it stable and in tune right up to 
+///Nyquist even when cutoff changes every sample. The band pass state
+///is soft limited so negative damping oscillates instead of blowing up.
+///
+#[derive(Clone, Copy)]
+pub struct Filter {
+    k:     SampleType, //Damping. 1 / Q.
+    a1:    SampleType,
+    a2:    SampleType,
+    a3:    SampleType,
+    ic1eq: SampleType, //Integrator states.
+    ic2eq: SampleType,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        let mut filter = Filter {
+            k: 0.0, a1: 0.0, a2: 0.0, a3: 0.0,
+            ic1eq: 0.0, ic2eq: 0.0,
+        };
+        filter.tune(1000.0, 0.707, 48000.0);
+        filter
+    }
+}
+
+impl Filter {
+///
+///Set cutoff in Hz and Q. Cutoff is limited to just below Nyquist and
+///Q to 0.5 or more.
+///
+    #[inline]
+    pub fn tune(&mut self, freq: SampleType, q: SampleType, rate: SampleType) {
+        let q = if q < 0.5 { 0.5 } else { q };
+        self.damping(freq, 1.0 / q, rate);
+    }
+
+///
+///Set cutoff in Hz and damping directly. Damping 2 is no resonance,
+///0 rings forever and below 0 self oscillates.
+///
+    #[inline]
+    pub fn damping(&mut self, freq: SampleType, k: SampleType, rate: SampleType) {
+        let max  = rate * 0.49;
+        let freq = if freq < 1.0 { 1.0 } else if freq > max { max } else { freq };
+
+        let g = tanf(PI * freq / rate);
+        self.k  = if k > 2.0 { 2.0 } else { k };
+        self.a1 = 1.0 / (1.0 + (g * (g + self.k)));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    #[inline]
+    pub fn tick(&mut self, v0: SampleType) -> Outputs {
+        let v3 = v0 - self.ic2eq;
+        let v1 = (self.a1 * self.ic1eq) + (self.a2 * v3);
+        let v2 = self.ic2eq + (self.a2 * self.ic1eq) + (self.a3 * v3);
+        self.ic1eq = LIMIT * tanhf(((2.0 * v1) - self.ic1eq) / LIMIT);
+        self.ic2eq = (2.0 * v2) - self.ic2eq;
+
+        if !self.ic1eq.is_finite() || !self.ic2eq.is_finite() {
+            self.reset();
+            return Outputs::default();
+        }
+
+        let hp = v0 - (self.k * v1) - v2;
+        Outputs { lp: v2, bp: v1, hp, notch: v2 + hp }
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1eq = 0.0;
+        self.ic2eq = 0.0;
+    }
+}
+
+/***********************************************************************
+ * Svf
+ **********************************************************************/
+
+///
+///Resonant state variable filter with low, band, high pass and notch 
+///on output ports 0..3. Mono process() gives the low pass.
+///
+///Cutoff and resonance are not smoothed. Coefficients are recomputed
+///on the next sample after a change so rack connections can sweep them
+///at audio rate. Resonance 1 rings indefinitely and above 1 the filter
+///oscillates on its own once excited.
+///
+pub struct Svf {
+    freq:   SampleType, //Cutoff in Hz.
+    res:    SampleType, //Resonance 0..1.1.
+    rate:   SampleType, //Sample rate.
+    dirty:  bool,       //Coefficients need recomputing.
+    filter: Filter,
+}
+
+impl Default for Svf {
+    fn default() -> Self {
+        let mut svf = Svf {
+            freq:   1000.0,
+            res:    0.0,
+            rate:   SAMPLE_RATE,
+            dirty:  true,
+            filter: Filter::default(),
+        };
+        svf.prepare(SAMPLE_RATE, 0);
+        svf
+    }
+}
+
+impl Effect for Svf {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        self.tick(smpl_in).lp
+    }
+
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        for (smpl_out, smpl_in) in output.iter_mut().zip(input.iter()) {
+            *smpl_out = self.tick(*smpl_in).lp;
+        }
+    }
+
+    fn num_outputs(&mut self) -> usize { 4 }
+
+///
+///Audio on port 0. Low, band, high pass and notch on ports 0..3.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        let input = match inputs.first() {
+            Some(input) => input,
+            None => return,
+        };
+
+        match outputs {
+            [lp, bp, hp, notch, ..] => {
+                for ((((smpl_lp, smpl_bp), smpl_hp), smpl_notch), smpl_in) in lp.iter_mut()
+                                                                            .zip(bp.iter_mut())
+                                                                            .zip(hp.iter_mut())
+                                                                            .zip(notch.iter_mut())
+                                                                            .zip(input.iter())
+                {
+                    let out = self.tick(*smpl_in);
+                    *smpl_lp    = out.lp;
+                    *smpl_bp    = out.bp;
+                    *smpl_hp    = out.hp;
+                    *smpl_notch = out.notch;
+                }
+            },
+            [lp, ..] => self.process_block(input, lp),
+            [] => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq(1000.0);
+        self.res(0.0);
+        self.filter.reset();
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.rate  = sample_rate;
+        self.dirty = true;
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.freq(val); },
+            1 => { self.res(val); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.freq),
+            1 => Ok(self.res),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Svf {
+    #[inline]
+    fn tick(&mut self, smpl_in: SampleType) -> Outputs {
+        if self.dirty {
+            self.filter.damping(self.freq, 2.0 * (1.0 - self.res), self.rate);
+            self.dirty = false;
+        }
+        self.filter.tick(smpl_in)
+    }
+
+///
+///Cutoff in Hz.
+///
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq  = hz;
+        new.dirty = true;
+        new
+    }
+
+///
+///Resonance. 0 none, 1 rings forever, above 1 self oscillates.
+///
+    pub fn res(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.res   = val;
+        new.dirty = true;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::svf::{Filter, Svf};
+    use crate::{Effect, SampleType};
+
+    #[test]
+    fn outputs() {
+//DC through low pass only. Band and high pass settle to zero.
+        let mut f = Filter::default();
+        f.tune(1000.0, 2.0, 48000.0);
+        let mut out = f.tick(1.0);
+        for _ in 0..48000 {
+            out = f.tick(1.0);
+        }
+        assert!((out.lp - 1.0).abs() < 1e-4);
+        assert!(out.bp.abs() < 1e-4);
+        assert!(out.hp.abs() < 1e-4);
+        assert!((out.notch - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn oscillates() {
+//Kick once then listen. Above 1 resonance the filter keeps ringing.
+        let mut svf = Svf::default();
+        svf.freq(440.0).res(1.05);
+        let input  = [0.0; 512];
+        let mut lp    = [0.0; 512];
+        let mut bp    = [0.0; 512];
+        let mut hp    = [0.0; 512];
+        let mut notch = [0.0; 512];
+        svf.process(1.0);
+        let mut max: SampleType = 0.0;
+        for n in 0..200 {
+            svf.process_ports(&[&input], &mut [&mut lp, &mut bp, &mut hp, &mut notch]);
+            if n > 150 {
+                max = lp.iter().fold(max, |m, v| m.max(v.abs()));
+            }
+        }
+        assert!(max > 0.5 && max < 10.0);
+    }
+}
@@ -22,6 +22,7 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
+use super::SampleType;
 use crate::Effect;
 
 /***********************************************************************
@@ -30,4 +31,13 @@ use crate::Effect;
 
 #[derive(Default)]
 pub struct Thru;
-impl Effect for Thru {}
+
+impl Effect for Thru {
+///
+///Copy input block to output block.
+///
+    fn process_block(&mut self, input: &[SampleType], output: &mut [SampleType]) {
+        let len = output.len().min(input.len());
+        output[..len].copy_from_slice(&input[..len]);
+    }
+}
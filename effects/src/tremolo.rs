@@ -0,0 +1,221 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use crate::Effect;
+use crate::param;
+use crate::param::{Desc, Unit, Curve, ERROR};
+use crate::smooth::Smooth;
+use crate::wave::{Phasor, Shape, SHAPE_MAX, wrap};
+
+static PARAMS: [Desc; 5] = [
+    Desc { name: "rate",   min: 0.05, max: 20.0,      default: 5.0, unit: Unit::Hz,    curve: Curve::Log },
+    Desc { name: "depth",  min: 0.0,  max: 1.0,       default: 0.5, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "shape",  min: 0.0,  max: SHAPE_MAX, default: 0.0, unit: Unit::None,  curve: Curve::Stepped },
+    Desc { name: "phase",  min: 0.0,  max: 1.0,       default: 0.0, unit: Unit::Ratio, curve: Curve::Linear },
+    Desc { name: "source", min: 0.0,  max: 1.0,       default: 0.0, unit: Unit::None,  curve: Curve::Stepped },
+];
+
+/***********************************************************************
+ * Tremolo
+ **********************************************************************/
+
+///
+///Tremolo. Stereo audio on ports 0 and 1 has its level swept by an LFO
+///with the right channel phase offset by phase cycles (0.5 pans back 
+///and forth). With source 1 the LFO is replaced by port 2 (-1..1), so 
+///a Sine connected there drives both channels.
+///
+pub struct Tremolo {
+    freq:     SampleType, //LFO rate in Hz.
+    depth:    Smooth,     //0 none .. 1 full cut at the bottom.
+    shape:    Shape,      //LFO waveform.
+    phase:    SampleType, //Right channel offset in cycles.
+    external: bool,       //LFO from port 2.
+    lfo:      Phasor,
+}
+
+impl Default for Tremolo {
+    fn default() -> Self {
+        let mut trem = Tremolo {
+            freq:     5.0,
+            depth:    Smooth::new(0.5),
+            shape:    Shape::Sine,
+            phase:    0.0,
+            external: false,
+            lfo:      Phasor::default(),
+        };
+        trem.prepare(SAMPLE_RATE, 0);
+        trem
+    }
+}
+
+impl Effect for Tremolo {
+    fn process(&mut self, smpl_in: SampleType) -> SampleType {
+        let depth = self.depth.next();
+        let lfo = self.shape.value(self.lfo.next(self.freq));
+        self.gain(depth, lfo) * smpl_in
+    }
+
+    fn num_inputs(&mut self) -> usize { 3 }
+    fn num_outputs(&mut self) -> usize { 2 }
+
+///
+///Left on port 0, right on port 1. External LFO on input port 2.
+///
+    fn process_ports(&mut self, 
+                     inputs: &[&[SampleType]], 
+                     outputs: &mut [&mut [SampleType]]) 
+    {
+        match (inputs, outputs) {
+            ([in_l, in_r, ext, ..], [out_l, out_r, ..]) => {
+                for ((((smpl_l, smpl_r), l), r), e) in out_l.iter_mut()
+                                                            .zip(out_r.iter_mut())
+                                                            .zip(in_l.iter())
+                                                            .zip(in_r.iter())
+                                                            .zip(ext.iter())
+                {
+                    let depth = self.depth.next();
+                    let phase = self.lfo.next(self.freq);
+                    let (lfo_l, lfo_r) = if self.external {
+                        (*e, *e)
+                    } else {
+                        (self.shape.value(phase), self.shape.value(wrap(phase + self.phase)))
+                    };
+                    *smpl_l = *l * self.gain(depth, lfo_l);
+                    *smpl_r = *r * self.gain(depth, lfo_r);
+                }
+            },
+            ([input, ..], [output, ..]) => self.process_block(input, output),
+            _ => {},
+        }
+    }
+
+    fn reset(&mut self) {
+        self.freq = 5.0;
+        self.depth.reset(0.5);
+        self.shape = Shape::Sine;
+        self.phase = 0.0;
+        self.external = false;
+        self.lfo.reset(0.0);
+    }
+
+    fn prepare(&mut self, sample_rate: SampleType, _max_block: usize) {
+        self.lfo.sample_rate(sample_rate);
+        self.depth.sample_rate(sample_rate);
+    }
+
+    fn params(&mut self) -> &'static [Desc] { &PARAMS }
+
+    fn set_param(&mut self, idx: usize, val: SampleType) -> Result<(), ERROR> {
+        let val = param::check(&PARAMS, idx, val)?;
+        match idx {
+            0 => { self.freq(val); },
+            1 => { self.depth(val); },
+            2 => { self.shape(Shape::from_param(val)); },
+            3 => { self.phase(val); },
+            4 => { self.external(val >= 0.5); },
+            _ => { return Err(ERROR::INDEX) }
+        }
+        Ok(())
+    }
+
+    fn get_param(&mut self, idx: usize) -> Result<SampleType, ERROR> {
+        match idx {
+            0 => Ok(self.freq),
+            1 => Ok(self.depth.target()),
+            2 => Ok(self.shape.to_param()),
+            3 => Ok(self.phase),
+            4 => Ok(if self.external { 1.0 } else { 0.0 }),
+            _ => Err(ERROR::INDEX)
+        }
+    }
+}
+
+impl Tremolo {
+///
+///Gain for an LFO value -1..1. The top of the cycle is unity.
+///
+    #[inline]
+    fn gain(&self, depth: SampleType, lfo: SampleType) -> SampleType {
+        let lfo = lfo.clamp(-1.0, 1.0);
+        1.0 - (depth * (0.5 - (0.5 * lfo)))
+    }
+
+    pub fn freq(&mut self, hz: SampleType) -> &mut Self {
+        let new = self;
+        new.freq = hz;
+        new
+    }
+
+    pub fn depth(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.depth.set(val);
+        new
+    }
+
+    pub fn shape(&mut self, shape: Shape) -> &mut Self {
+        let new = self;
+        new.shape = shape;
+        new
+    }
+
+///
+///Right channel LFO offset in cycles.
+///
+    pub fn phase(&mut self, val: SampleType) -> &mut Self {
+        let new = self;
+        new.phase = wrap(val);
+        new
+    }
+
+///
+///Take the LFO from input port 2.
+///
+    pub fn external(&mut self, on: bool) -> &mut Self {
+        let new = self;
+        new.external = on;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tremolo::Tremolo;
+    use crate::Effect;
+
+    #[test]
+    fn stereo_phase() {
+//Half a cycle apart the channels' gains sum to a constant.
+        let mut trem = Tremolo::default();
+        trem.depth(1.0).phase(0.5);
+        let audio = [1.0; 4800];
+        let ext   = [0.0; 4800];
+        let (mut l, mut r) = ([0.0; 4800], [0.0; 4800]);
+        trem.process_ports(&[&audio, &audio, &ext], &mut [&mut l, &mut r]);
+        for (a, b) in l.iter().zip(r.iter()).skip(4000) {
+            assert!((a + b - 1.0).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/*
+MIT License
+
+Copyright (c) 2019 Richard A. Healy
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use super::{SAMPLE_RATE, SampleType};
+use libm::sinf;
+use core::f32::consts::PI;
+
+/***********************************************************************
+ * Shape
+ **********************************************************************/
+
+///
+///Low frequency waveforms shared by the modulation effects.
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sine,
+    Triangle,
+    Square,
+    SawUp,
+    SawDown,
+}
+
+///
+///Highest shape parameter value.
+///
+pub const SHAPE_MAX: SampleType = 4.0;
+
+impl Shape {
+///
+///Shape from parameter value. Values are rounded and out of range 
+///values pick the nearest shape.
+///
+    pub fn from_param(val: SampleType) -> Shape {
+        match (val + 0.5) as usize {
+            0 => Shape::Sine,
+            1 => Shape::Triangle,
+            2 => Shape::Square,
+            3 => Shape::SawUp,
+            _ => Shape::SawDown,
+        }
+    }
+
+    pub fn to_param(&self) -> SampleType {
+        match self {
+            Shape::Sine     => 0.0,
+            Shape::Triangle => 1.0,
+            Shape::Square   => 2.0,
+            Shape::SawUp    => 3.0,
+            Shape::SawDown  => 4.0,
+        }
+    }
+
+///
+///Value -1..1 at phase 0..1.
+///
+    #[inline]
+    pub fn value(&self, phase: SampleType) -> SampleType {
+        match self {
+            Shape::Sine     => sinf(2.0 * PI * phase),
+            Shape::Triangle => {
+                if phase < 0.25 {
+                    4.0 * phase
+                } else if phase < 0.75 {
+                    2.0 - (4.0 * phase)
+                } else {
+                    (4.0 * phase) - 4.0
+                }
+            },
+            Shape::Square   => if phase < 0.5 { 1.0 } else { -1.0 },
+            Shape::SawUp    => (2.0 * phase) - 1.0,
+            Shape::SawDown  => 1.0 - (2.0 * phase),
+        }
+    }
+}
+
+/***********************************************************************
+ * Phasor
+ **********************************************************************/
+
+///
+///Phase accumulator 0..1.
+///
+pub struct Phasor {
+    phase: SampleType, //Position in cycle 0..1.
+    rate:  SampleType, //Sample rate.
+}
+
+impl Default for Phasor {
+    fn default() -> Self {
+        Phasor {
+            phase: 0.0,
+            rate:  SAMPLE_RATE,
+        }
+    }
+}
+
+///
+/// Return fractional part of val 0..1.
+///
+#[inline]
+pub fn wrap(val: SampleType) -> SampleType {
+    let val = val - ((val as i32) as SampleType);
+    if val < 0.0 { val + 1.0 } else { val }
+}
+
+impl Phasor {
+    pub fn sample_rate(&mut self, rate: SampleType) -> &mut Self {
+        let new = self;
+        new.rate = rate;
+        new
+    }
+
+    pub fn reset(&mut self, phase: SampleType) {
+        self.phase = wrap(phase);
+    }
+
+    #[inline]
+    pub fn phase(&self) -> SampleType {
+        self.phase
+    }
+
+///
+///Return current phase then advance by freq Hz.
+///
+    #[inline]
+    pub fn next(&mut self, freq: SampleType) -> SampleType {
+        let phase = self.phase;
+        self.phase = wrap(self.phase + (freq / self.rate));
+        phase
+    }
+}
@@ -189,18 +189,20 @@ fn main() -> ! {
     let mut tx  = queue::Tx::default();
     let mut u0  = rack::Unit::new();
 
+    u0.prepare(SAMPLE_RATE_USIZE as SampleType);
+
     debug::out("rpi3fxproc::main(): Connecting effects.\r\n");
 
     let connections: [[usize;3]; 4] = [
-        [rack::INPUT_A, 4, 100],  //From Input A to delay0.
-        [4, rack::OUTPUT_A, 100], //From delay0 to Output A
-        [rack::INPUT_B, 5, 100],  //From Input B to delay1.
-        [5, rack::OUTPUT_B, 100], //From delay1 to Output B
+        [rack::INPUT_A, 4, 0],  //From Input A to delay0.
+        [4, rack::OUTPUT_A, 0], //From delay0 to Output A
+        [rack::INPUT_B, 5, 0],  //From Input B to delay1.
+        [5, rack::OUTPUT_B, 0], //From delay1 to Output B
     ];
 
     for conn in connections.iter() {
-        if let Err(err) = u0.from(conn[0])        //Input A
-                            .to(conn[1], conn[2]) //Process input of delay 0
+        if let Err(err) = u0.from(conn[0])             //From effect
+                            .to_port(conn[1], conn[2]) //Input port of effect
                             .connect()
         {
             debug::out(err);
@@ -242,6 +244,11 @@ fn main() -> ! {
         debug::u64hex(tx.errcnt as u64);
         debug::out("\r\n");
         tx.errcnt = 0;
+
+        debug::out("rack errcnt: ");
+        debug::u64hex(u0.errcnt as u64);
+        debug::out("\r\n");
+        u0.errcnt = 0;
     }
 }
 
@@ -23,13 +23,15 @@ SOFTWARE.
 */
 
 use alloc::rc::Rc;
+use alloc::vec;
 use alloc::vec::Vec;
 
 use core::cell::RefCell;
 use core::default::Default;
 
 use effects;
-use effects::SampleType;
+use effects::{SampleType, MAX_PORTS};
+use effects::param;
 use effects::thru::*;
 use effects::delay::*;
 use effects::pwm::*;
@@ -37,29 +39,29 @@ use effects::sine::*;
 use effects::constant::*;
 
 use common::buffer::Buffer;
-use common::buffer::{Read, Write};
+use common::buffer::{Read, Write, Size, Amount};
 
 use peripherals::debug;
 
 pub mod connector {
-    use effects::SampleType;
-
 ///
-///Connector from a specific effect.
+///Connector from a specific effect and one of its output ports.
 ///
     #[derive(Default, Clone, Copy, PartialEq)]
     pub struct From {
         pub effect: usize, //Index into rack effects array.
-        pub param: usize,  //Set to the "To" effect parameter.
+        pub port: usize,   //Output port of the effect.
     }
 
 ///
-///Connector to a specific effect and its input.
+///Connector to a specific effect and its input. The input is either a
+///parameter or, when param is the process value, an input port.
 ///
     #[derive(Default, Clone, Copy, PartialEq)]
     pub struct To {
         pub effect: usize, //Index into rack effects array.
         pub param: usize,  //Parameter offset.
+        pub port: usize,   //Input port of the effect.
     }
 }
 
@@ -77,6 +79,9 @@ pub mod connection {
 ///                       .to(2, 2)
 ///                       .connect();
 /// { ... }
+///
+///to() connects to an effect parameter and fails if the parameter 
+///doesn't exist. to_port() connects to an effect input port.
 ///
     pub mod factory {
         use super::connector;
@@ -88,10 +93,17 @@ pub mod connection {
         pub trait From<'a>
         {
             fn from(&'a mut self, _effect: usize) -> 
-                FromResult<Self> 
+                FromResult<'a, Self> 
             {
                 Err("connection::factory::from() not implemented.")
             }
+
+            #[allow(clippy::wrong_self_convention)]
+            fn from_port(&'a mut self, _effect: usize, _port: usize) -> 
+                FromResult<'a, Self> 
+            {
+                Err("connection::factory::from_port() not implemented.")
+            }
         }
 
 //To
@@ -101,10 +113,16 @@ pub mod connection {
         pub trait To<'a, T>
         {
             fn to(&'a mut self, _eff: usize, _param: usize) -> 
-                ToResult<T>
+                ToResult<'a, T>
             {
                 Err("connection::factory::to() not implemented.")
             }
+
+            fn to_port(&'a mut self, _eff: usize, _port: usize) -> 
+                ToResult<'a, T>
+            {
+                Err("connection::factory::to_port() not implemented.")
+            }
         }
 
 //Connect
@@ -132,7 +150,8 @@ pub mod connection {
 ///information about which effect is connected to "this" effect and
 ///which parameter or input it is connected to. The To connections
 ///provide information about which effects and parameters will receive
-///the output from this effect.
+///the output from this effect. Both lists hold the complete from/to
+///pair so the ports on either end are known.
 ///
 mod connections {
     use super::connector;
@@ -140,8 +159,26 @@ mod connections {
 
     #[derive(Default, Clone)]
     pub struct Effect {
-        pub from: Vec<connector::From>, //From connections.
-        pub to: Vec<connector::To>,     //To connections.
+        pub from: Vec<(connector::From, connector::To)>, //From connections.
+        pub to: Vec<(connector::From, connector::To)>,   //To connections.
+    }
+}
+
+///
+///Each effect has a block of values for every input port and every
+///parameter. Connections to the same input or parameter are summed 
+///into its block.
+///
+mod values {
+    use super::BLOCK_LEN;
+    use alloc::vec::Vec;
+    use effects::SampleType;
+
+    #[derive(Default, Clone)]
+    pub struct Effect {
+        pub inputs: Vec<[SampleType; BLOCK_LEN]>, //One block per input port.
+        pub params: Vec<[SampleType; BLOCK_LEN]>, //One block per parameter.
+        pub num_outputs: usize,                   //Number of output ports.
     }
 }
 
@@ -159,14 +196,14 @@ pub const OUTPUT_A: usize = 2;
 pub const OUTPUT_B: usize = 3;
 
 ///
-///Number of parameter values to store + 1 for process value.
+///Parameter value reserved for connections to an effect's input ports.
 ///
-const NUM_VALUES: usize = 8;
+const PROCESS_VALUE: usize = usize::MAX;
 
 ///
-///Last value is set aside for passing to the effect's "process" function.
+///Number of sample pairs processed through the graph at a time.
 ///
-const PROCESS_VALUE: usize = NUM_VALUES - 1;
+pub const BLOCK_LEN: usize = 32;
 
 ///
 ///Hardcoded "rack unit" containing effects. 
@@ -174,14 +211,23 @@ const PROCESS_VALUE: usize = NUM_VALUES - 1;
 pub struct Unit {
     effects: [Rc<RefCell<dyn effects::Effect>>; NUM_EFFECTS],
     conns:   Vec<connections::Effect>,
-    values:  [[SampleType; NUM_VALUES]; NUM_EFFECTS],
+    values:  Vec<values::Effect>,
     queue:   Vec<usize>,
-    ab:      bool,
+
+    pub errcnt: usize, //Parameter errors encountered while processing.
+}
+
+impl Default for Unit {
+    fn default() -> Self {
+        Unit::new()
+    }
 }
 
 impl Unit {
 ///
-///Process one sample pair's worth through the graph.
+///Process as many whole blocks of sample pairs as the input queue
+///holds and the output queue has room for. Returns the number of
+///samples dequeued.
 ///
     pub fn process(&mut self,
                    in_q: &mut Buffer<SampleType>,
@@ -189,84 +235,106 @@ impl Unit {
     {
         let mut cnt: usize = 0;
 
-        while !out_q.full_queue() && !in_q.empty_queue() 
-        {
-            if self.ab { //Both inputs filled. Process.
-                self.values[INPUT_B][PROCESS_VALUE] = in_q.dequeue();
-
-                for effect_idx in self.queue.iter() {
-                    let mut effect = self.effects[*effect_idx].borrow_mut();
-
-//Update current effect parameters.
-                    for conn in self.conns[*effect_idx].from.iter_mut() {
-                        if conn.param != PROCESS_VALUE {
-                            effect.set_param (
-                                conn.param, 
-                                self.values[*effect_idx][conn.param]
-                            );
-//Connections to the same parameter are summed. Clear for next pass.
-                            self.values[*effect_idx][conn.param] = SampleType::default();
-                        }
-                    }
+        loop {
+            let len = (in_q.amt() / 2)
+                          .min((out_q.size() - out_q.amt()) / 2)
+                          .min(BLOCK_LEN);
 
-//Process. Clear for next pass.
-//                     debug::out("Processing idx: ");
-//                     debug::u32hex((*effect_idx) as u32);
-//                     debug::out("\r\n");
-//                     debug::out("Input = ");
-//                     debug::u32hex(self.values[*effect_idx][PROCESS_VALUE] as u32);
-//                     debug::out("\r\n");
-
-                    let result = effect.process(self.values[*effect_idx][PROCESS_VALUE]);
-                    self.values[*effect_idx][PROCESS_VALUE] = SampleType::default();
-
-//                     debug::out("Output = ");
-//                     debug::u32hex(result as u32);
-//                     debug::out("\r\n");
-
-//                     debug::out("self.values PROCESS_VALUE = ");
-//                     debug::u32hex(self.values[*effect_idx][PROCESS_VALUE] as u32);
-//                     debug::out("\r\n");
-
-//Dispatch result to downstream connections.
-                    for conn in self.conns[*effect_idx].to.iter_mut() {
-//                         debug::out("Conn effect idx: ");
-//                         debug::u32hex(conn.effect as u32);
-//                         debug::out(" param idx: ");
-//                         debug::u32hex(conn.param as u32);
-//                         debug::out("\r\n");
-//                         debug::out("Value = ");
-//                         debug::u32hex(result as u32);
-//                         debug::out("\r\n");
-//                         debug::out("self.values pre = ");
-//                         debug::u32hex(self.values[conn.effect][conn.param] as u32);
-//                         debug::out("\r\n");
-                        self.values[conn.effect][conn.param] += result;
-//                         debug::out("self.values post = ");
-//                         debug::u32hex(self.values[conn.effect][conn.param] as u32);
-//                         debug::out("\r\n");
+            if len == 0 {
+                break;
+            }
+
+            for i in 0..len {
+                self.values[INPUT_A].inputs[0][i] = in_q.dequeue();
+                self.values[INPUT_B].inputs[0][i] = in_q.dequeue();
+            }
+
+            self.process_block(len);
+
+            for i in 0..len {
+                out_q.enqueue(self.values[OUTPUT_A].inputs[0][i]);
+                out_q.enqueue(self.values[OUTPUT_B].inputs[0][i]);
+                self.values[OUTPUT_A].inputs[0][i] = SampleType::default();
+                self.values[OUTPUT_B].inputs[0][i] = SampleType::default();
+            }
+
+            cnt += len * 2;
+        }
+
+        cnt
+    }
+
+///
+///Process one block of len samples through the graph. Effects without
+///parameter connections process the whole block in one call. Effects
+///with parameter connections are stepped one sample at a time so the
+///parameters follow their sources at the audio rate.
+///
+    fn process_block(&mut self, len: usize) {
+        let mut outputs = [[SampleType::default(); BLOCK_LEN]; MAX_PORTS];
+
+        for effect_idx in self.queue.iter() {
+            let mut effect = self.effects[*effect_idx].borrow_mut();
+            let values     = &mut self.values[*effect_idx];
+            let conns      = &self.conns[*effect_idx];
+
+            if conns.from.iter().all(|(_, to)| to.param == PROCESS_VALUE) {
+                process_ports(&mut *effect, values, &mut outputs, 0, len);
+            } else {
+                for i in 0..len {
+//Update current effect parameters. Out of range values are clamped.
+//Errors are counted instead of halting processing. The block already
+//holds the sum of every connection to a parameter so each parameter
+//is set once, by its first connection.
+                    for (n, (_, to)) in conns.from.iter().enumerate() {
+                        if to.param != PROCESS_VALUE &&
+                           !conns.from[..n].iter().any(|(_, prev)| prev.param == to.param)
+                        {
+                            let val = values.params[to.param][i];
+                            match effect.set_param(to.param, val) {
+                                Ok(_) => {},
+                                Err(param::ERROR::RANGE) => {
+                                    let val = effect.params()[to.param].clamp(val);
+                                    let _ = effect.set_param(to.param, val);
+                                    self.errcnt += 1;
+                                },
+                                Err(_) => {
+                                    self.errcnt += 1;
+                                }
+                            }
+                        }
                     }
+                    process_ports(&mut *effect, values, &mut outputs, i, i + 1);
                 }
+            }
 
-//                 debug::out("[OutputA, OutputB] = [");
-//                 debug::u32hex(self.values[OUTPUT_A][PROCESS_VALUE] as u32);
-//                 debug::out(",");
-//                 debug::u32hex(self.values[OUTPUT_B][PROCESS_VALUE] as u32);
-//                 debug::out("]\r\n");
- 
-                out_q.enqueue(self.values[OUTPUT_A][PROCESS_VALUE]);
-                out_q.enqueue(self.values[OUTPUT_B][PROCESS_VALUE]);
-                self.values[OUTPUT_A][PROCESS_VALUE] = SampleType::default();
-                self.values[OUTPUT_B][PROCESS_VALUE] = SampleType::default();
+//Connections to the same input or parameter are summed. Clear for next pass.
+            for (_, to) in conns.from.iter() {
+                let val = if to.param == PROCESS_VALUE {
+                    &mut values.inputs[to.port]
+                } else {
+                    &mut values.params[to.param]
+                };
 
-            } else {
-                self.values[INPUT_A][PROCESS_VALUE] = in_q.dequeue();
+                for val in val[..len].iter_mut() {
+                    *val = SampleType::default();
+                }
             }
 
-            cnt += 1;
-            self.ab = !self.ab;
+//Dispatch results to downstream connections.
+            for (from, to) in conns.to.iter() {
+                let src = &outputs[from.port];
+                let dst = if to.param == PROCESS_VALUE {
+                    &mut self.values[to.effect].inputs[to.port]
+                } else {
+                    &mut self.values[to.effect].params[to.param]
+                };
+
+                for i in 0..len {
+                    dst[i] += src[i];
+                }
+            }
         }
-        cnt
     }
 
 ///
@@ -275,10 +343,10 @@ impl Unit {
     pub fn new() -> Unit {
         let mut unit = Unit {
             effects: [
-                Rc::new(RefCell::new(Thru::default())), //INPUT_A
-                Rc::new(RefCell::new(Thru::default())), //INPUT_B
-                Rc::new(RefCell::new(Thru::default())), //OUTPUT_A
-                Rc::new(RefCell::new(Thru::default())), //OUTPUT_B
+                Rc::new(RefCell::new(Thru)), //INPUT_A
+                Rc::new(RefCell::new(Thru)), //INPUT_B
+                Rc::new(RefCell::new(Thru)), //OUTPUT_A
+                Rc::new(RefCell::new(Thru)), //OUTPUT_B
                 Rc::new(RefCell::new(Delay::default())),
                 Rc::new(RefCell::new(Delay::default())),
                 Rc::new(RefCell::new(Delay::default())), 
@@ -294,25 +362,44 @@ impl Unit {
             ],
 
             conns:  Vec::<connections::Effect>::default(),
-            values: [[SampleType::default(); NUM_VALUES]; NUM_EFFECTS],
+            values: Vec::<values::Effect>::default(),
             queue:  Vec::<usize>::default(),
-            ab: false,
+            errcnt: 0,
         };
 
         for i in 0..NUM_EFFECTS {
-            unit.effects[i].borrow_mut().reset();
+            let mut effect = unit.effects[i].borrow_mut();
+            effect.reset();
+            effect.prepare(effects::SAMPLE_RATE, BLOCK_LEN);
+
+            let num_inputs  = effect.num_inputs().min(MAX_PORTS);
+            let num_outputs = effect.num_outputs().min(MAX_PORTS);
+            let num_params  = effect.num_params();
+
             unit.conns.push(connections::Effect::default());
+            unit.values.push(values::Effect {
+                inputs: vec![[SampleType::default(); BLOCK_LEN]; num_inputs],
+                params: vec![[SampleType::default(); BLOCK_LEN]; num_params],
+                num_outputs,
+            });
         }
 
         unit
     }
 
+///
+///Prepare all effects for a new sample rate. Call before processing 
+///whenever the codec sample rate changes.
+///
+    pub fn prepare(&mut self, sample_rate: SampleType) {
+        for effect in self.effects.iter() {
+            effect.borrow_mut().prepare(sample_rate, BLOCK_LEN);
+        }
+    }
+
     pub fn queue(&mut self, effect: usize) -> Result< (), &'static str > {
         if effect < NUM_EFFECTS {
-            if let None = self.queue
-                              .iter()
-                              .position(|&val| val == effect) 
-            {
+            if !self.queue.contains(&effect) {
                 self.queue.push(effect);
                 Ok(())
             } else {
@@ -326,19 +413,29 @@ impl Unit {
 
 impl <'a> connection::factory::From<'a> for Unit {
     fn from(&'a mut self, eff: usize) -> 
-        connection::factory::FromResult<Self> 
+        connection::factory::FromResult<'a, Self> 
     {
-        if eff < self.effects.len() {
-            Ok(( 
-                self, 
-                connector::From { 
-                    effect: eff, 
-                    param: PROCESS_VALUE
-                } 
-            ))
-        } else {
-            Err("connection::factory::from(): Effect out of range.")
+        self.from_port(eff, 0)
+    }
+
+    fn from_port(&'a mut self, eff: usize, port: usize) -> 
+        connection::factory::FromResult<'a, Self> 
+    {
+        if eff >= self.effects.len() {
+            return Err("connection::factory::from(): Effect out of range.")
+        }
+
+        if port >= self.values[eff].num_outputs {
+            return Err("connection::factory::from(): Port out of range.")
         }
+
+        Ok(( 
+            self, 
+            connector::From { 
+                effect: eff, 
+                port
+            } 
+        ))
     }
 }
 
@@ -346,7 +443,7 @@ impl <'a> connection::factory::To<'a, Unit> for
     connection::factory::FromResult<'a, Unit>
 {
     fn to(&'a mut self, effect: usize, param: usize) -> 
-        connection::factory::ToResult<Unit>
+        connection::factory::ToResult<'a, Unit>
     {
         match self {
             Ok((unit, from)) => {
@@ -354,27 +451,48 @@ impl <'a> connection::factory::To<'a, Unit> for
                     return Err("connection::factory::to(): Effect out of range.")
                 }
 
-//Determine if param is an effect parameter or the effect processing input.
-                if param < unit.effects[effect]
-                               .borrow_mut()
-                               .num_params() 
+//Parameter must exist. Input ports are connected with to_port().
+                if let Err(err) = unit.effects[effect]
+                                      .borrow_mut()
+                                      .get_param(param)
                 {
-                    Ok ((
-                        unit, *from,
-                        connector::To { 
-                            effect: effect, 
-                            param: param
-                        }
-                    ))
-                } else {
-                    Ok (( 
-                        unit, *from,
-                        connector::To { 
-                            effect: effect, 
-                            param: PROCESS_VALUE
-                        }
-                    ))
+                    return Err(err.msg())
                 }
+
+                Ok ((
+                    unit, *from,
+                    connector::To { 
+                        effect, 
+                        param,
+                        port: 0
+                    }
+                ))
+            }
+            Err(err) => Err(err)
+        }
+    }
+
+    fn to_port(&'a mut self, effect: usize, port: usize) -> 
+        connection::factory::ToResult<'a, Unit>
+    {
+        match self {
+            Ok((unit, from)) => {
+                if effect >= unit.effects.len() {
+                    return Err("connection::factory::to_port(): Effect out of range.")
+                }
+
+                if port >= unit.values[effect].inputs.len() {
+                    return Err("connection::factory::to_port(): Port out of range.")
+                }
+
+                Ok ((
+                    unit, *from,
+                    connector::To { 
+                        effect, 
+                        param: PROCESS_VALUE,
+                        port
+                    }
+                ))
             }
             Err(err) => Err(err)
         }
@@ -389,24 +507,11 @@ impl <'a> connection::factory::Connect<'a> for
     {
         match self {
             Ok((unit, from, to)) => {
-                if let None = unit.conns[from.effect]
-                                  .to
-                                  .iter()
-                                  .position(|&val| val == *to) 
-                {
-                    if let None = unit.conns[to.effect]
-                                      .from
-                                      .iter()
-                                      .position(|&val| val == *from)
-                    {
-                        unit.conns[from.effect].to.push(*to);
-                        unit.conns[to.effect].from.push (
-                            connector::From {
-                                effect: from.effect, 
-                                param: to.param, //Where to look in the value array for parameter. 
-                            }
-                        );
-                        return Ok(());
+                if !unit.conns[from.effect].to.contains(&(*from, *to)) {
+                    if !unit.conns[to.effect].from.contains(&(*from, *to)) {
+                        unit.conns[from.effect].to.push((*from, *to));
+                        unit.conns[to.effect].from.push((*from, *to));
+                        Ok(())
                     } else {
                         Err("connection::factory::connect(): To already connected to From!")
                     }
@@ -430,16 +535,16 @@ impl <'a> connection::factory::Disconnect<'a> for
                 if let Some(f) = unit.conns[from.effect]
                                      .to
                                      .iter()
-                                     .position(|&val| val == *to) 
+                                     .position(|&val| val == (*from, *to)) 
                 {
                     if let Some(t) = unit.conns[to.effect]
                                          .from
                                          .iter()
-                                         .position(|&val| val == *from)
+                                         .position(|&val| val == (*from, *to))
                     {
                         unit.conns[from.effect].to.remove(f);
                         unit.conns[to.effect].from.remove(t);
-                        return Ok(());
+                        Ok(())
                     } else {
                         Err("connection::factory::disconnect(): To not connected to From!")
                     }
@@ -451,3 +556,31 @@ impl <'a> connection::factory::Disconnect<'a> for
         }
     }
 }
+
+///
+///Hand the blocks of an effect's input ports and the output blocks to 
+///process_ports() for samples start..end.
+///
+fn process_ports(effect:  &mut dyn effects::Effect,
+                 values:  &values::Effect,
+                 outputs: &mut [[SampleType; BLOCK_LEN]; MAX_PORTS],
+                 start:   usize,
+                 end:     usize)
+{
+    let mut inputs: [&[SampleType]; MAX_PORTS] = [&[]; MAX_PORTS];
+
+    for (input, block) in inputs.iter_mut().zip(values.inputs.iter()) {
+        *input = &block[start..end];
+    }
+
+    let mut outs: [&mut [SampleType]; MAX_PORTS] = Default::default();
+
+    for (out, block) in outs.iter_mut()
+                            .zip(outputs.iter_mut().map(|o| &mut o[start..end])) 
+    {
+        *out = block;
+    }
+
+    effect.process_ports(&inputs[..values.inputs.len()], 
+                         &mut outs[..values.num_outputs]);
+}